@@ -0,0 +1,125 @@
+use std::fmt;
+
+use serde::{
+    Deserialize, Deserializer, Serialize,
+    de::{self, MapAccess, Visitor},
+};
+
+use crate::model::{GlyphOptions, GlyphTransform};
+
+/// A `[glyphs]` or `[local_assets]` value, either just the icon or a table with options.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub(crate) enum GlyphEntry {
+    Icon(String),
+    Table(GlyphTable),
+}
+
+// Untagged deserialization would hide which table field is wrong
+impl<'de> Deserialize<'de> for GlyphEntry {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntryVisitor;
+
+        impl<'de> Visitor<'de> for EntryVisitor {
+            type Value = GlyphEntry;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("an icon name or a table with `icon` and glyph options")
+            }
+
+            fn visit_str<E: de::Error>(self, icon: &str) -> Result<Self::Value, E> {
+                Ok(GlyphEntry::Icon(icon.to_string()))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                GlyphTable::deserialize(de::value::MapAccessDeserializer::new(map))
+                    .map(GlyphEntry::Table)
+            }
+        }
+
+        deserializer.deserialize_any(EntryVisitor)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct GlyphTable {
+    /// `collection::icon` in `[glyphs]`, the asset name in `[local_assets]`.
+    pub icon: String,
+    /// Fixed codepoint, e.g. to match a legacy icon font.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub codepoint: Option<u32>,
+    /// Use the icon's natural aspect ratio for its advance width.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub proportional: Option<bool>,
+    /// Scale factor around the center of the icon.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f64>,
+    /// Horizontal shift as a fraction of the icon width, positive moves right.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset_x: Option<f64>,
+    /// Vertical shift as a fraction of the icon height, positive moves up.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub offset_y: Option<f64>,
+    /// Clockwise rotation in degrees.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotate: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flip_h: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flip_v: Option<bool>,
+    /// Space around the icon as a fraction of its size.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub padding: Option<f64>,
+}
+
+impl GlyphEntry {
+    pub(crate) fn icon(&self) -> &str {
+        match self {
+            GlyphEntry::Icon(icon) => icon,
+            GlyphEntry::Table(table) => &table.icon,
+        }
+    }
+
+    pub(crate) fn codepoint(&self) -> Option<u32> {
+        match self {
+            GlyphEntry::Icon(_) => None,
+            GlyphEntry::Table(table) => table.codepoint,
+        }
+    }
+
+    pub(crate) fn options(&self) -> Result<GlyphOptions, String> {
+        let GlyphEntry::Table(table) = self else {
+            return Ok(GlyphOptions::default());
+        };
+
+        let defaults = GlyphTransform::default();
+        let transform = GlyphTransform {
+            scale: table.scale.unwrap_or(defaults.scale),
+            offset_x: table.offset_x.unwrap_or(defaults.offset_x),
+            offset_y: table.offset_y.unwrap_or(defaults.offset_y),
+            rotate: table.rotate.unwrap_or(defaults.rotate),
+            flip_h: table.flip_h.unwrap_or(defaults.flip_h),
+            flip_v: table.flip_v.unwrap_or(defaults.flip_v),
+            padding: table.padding.unwrap_or(defaults.padding),
+        };
+        if !(transform.scale.is_finite() && transform.scale > 0.0) {
+            return Err(format!("scale must be positive (got {})", transform.scale));
+        }
+        if !(transform.padding.is_finite() && transform.padding >= 0.0) {
+            return Err(format!(
+                "padding must not be negative (got {})",
+                transform.padding
+            ));
+        }
+        let finite = [transform.offset_x, transform.offset_y, transform.rotate];
+        if !finite.iter().all(|value| value.is_finite()) {
+            return Err("offsets and rotation must be finite".to_string());
+        }
+
+        Ok(GlyphOptions {
+            proportional: table.proportional,
+            transform,
+        })
+    }
+}
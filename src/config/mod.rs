@@ -1,3 +1,4 @@
+mod entry;
 mod parser;
 
 pub(crate) use parser::parse_definition;
@@ -1,45 +1,204 @@
-use crate::model::{Collection, Definition, PackIcon};
+use super::entry::GlyphEntry;
+use crate::model::{
+    CodepointConfig, Collection, Definition, FontConfig, FontFormat, GlyphOptions, IconifyConfig,
+    PackIcon, REGULAR_WEIGHT,
+};
 use crate::utils::{hex_upper, reserved_name, upper_first_char};
+use crate::{Error, ICED_TEMPLATE};
 use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
-use std::{collections::BTreeMap, fs, path::Path};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::Path,
+};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct DefinitionTemp {
     module: String,
-    glyphs: IndexMap<String, String>,
-    local_assets: IndexMap<String, String>,
+    glyphs: IndexMap<String, GlyphEntry>,
+    local_assets: IndexMap<String, GlyphEntry>,
+    #[serde(default)]
+    font: FontConfig,
+    #[serde(default)]
+    codepoints: CodepointConfig,
+    /// Where icons are fetched from does not change the output, so it is not hashed
+    #[serde(default, skip_serializing)]
+    iconify: IconifyConfig,
 }
 
-pub(crate) fn parse_definition(path: &Path, assets_path: Option<&Path>) -> (Definition, String) {
-    let content = fs::read_to_string(path).unwrap_or_else(|err| {
-        panic!("Failed to read file: {}", err);
-    });
+fn glyph_options(glyph: &str, entry: &GlyphEntry) -> Result<GlyphOptions, Error> {
+    entry.options().map_err(|message| Error::InvalidGlyph {
+        glyph: glyph.to_string(),
+        message,
+    })
+}
+
+/// Validate the codepoint pinned by a glyph entry, if any.
+fn pinned_codepoint(
+    glyph: &str,
+    entry: &GlyphEntry,
+    codepoints: &CodepointConfig,
+    pinned: &mut HashSet<u32>,
+) -> Result<Option<u32>, Error> {
+    let Some(codepoint) = entry.codepoint() else {
+        return Ok(None);
+    };
+
+    let invalid = |message| Error::InvalidGlyph {
+        glyph: glyph.to_string(),
+        message,
+    };
+    if !codepoints.range.bounds().contains(&codepoint) {
+        return Err(invalid(format!(
+            "codepoint U+{codepoint:04X} is outside {}",
+            codepoints.describe()
+        )));
+    }
+    if !pinned.insert(codepoint) {
+        return Err(invalid(format!(
+            "codepoint U+{codepoint:04X} is pinned by another glyph"
+        )));
+    }
+
+    Ok(Some(codepoint))
+}
+
+/// Validate `[font.weights]` and the variable font options using them.
+fn check_font_weights(path: &Path, font: &FontConfig) -> Result<(), Error> {
+    let config_error = |message: String| Error::Config {
+        path: path.to_path_buf(),
+        message,
+    };
+
+    let mut seen = HashSet::new();
+    let mut constants = HashSet::new();
+    for (name, weight) in &font.weights {
+        let valid_name = !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+        if !valid_name || name.eq_ignore_ascii_case("regular") {
+            return Err(config_error(format!(
+                "invalid weight name '{name}', expected letters, digits, '-' or '_' other than 'regular'"
+            )));
+        }
+        // Names end up as `FONT_<NAME>` constants of the Iced module
+        if !constants.insert(name.to_ascii_uppercase().replace('-', "_")) {
+            return Err(config_error(format!(
+                "weight name '{name}' only differs from another one in case or '-' and '_'"
+            )));
+        }
+        if !(1..=1000).contains(&weight.weight) || weight.weight == REGULAR_WEIGHT {
+            return Err(config_error(format!(
+                "weight '{name}' must be between 1 and 1000 and not {REGULAR_WEIGHT}, the weight of the SVGs"
+            )));
+        }
+        if !seen.insert(weight.weight) {
+            return Err(config_error(format!(
+                "weight {} is used twice",
+                weight.weight
+            )));
+        }
+        if !weight.stroke.is_finite() || weight.stroke <= 0.0 {
+            return Err(config_error(format!(
+                "stroke of weight '{name}' must be greater than 0"
+            )));
+        }
+    }
+
+    if font.variable {
+        let message = if font.weights.is_empty() {
+            Some("a variable font needs at least one entry in [font.weights]")
+        } else if font.remove_overlaps {
+            Some("a variable font cannot use 'remove_overlaps'")
+        } else if !font.formats.contains(&FontFormat::Ttf) {
+            Some("a variable font needs the 'ttf' format")
+        } else {
+            None
+        };
+        if let Some(message) = message {
+            return Err(config_error(message.to_string()));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn parse_definition(
+    path: &Path,
+    assets_path: Option<&Path>,
+) -> Result<(Definition, String), Error> {
+    let content = fs::read_to_string(path).map_err(|err| Error::Config {
+        path: path.to_path_buf(),
+        message: format!("failed to read file: {err}"),
+    })?;
 
-    let definition: DefinitionTemp = toml::from_str(&content).unwrap_or_else(|err| {
-        panic!("Failed to parse TOML: {}", err);
-    });
+    let definition: DefinitionTemp = toml::from_str(&content).map_err(|err| Error::Config {
+        path: path.to_path_buf(),
+        message: format!("failed to parse TOML: {err}"),
+    })?;
 
     let serialized = serde_json::to_vec(&definition).expect("Failed to serialize definition");
-    let hash = hex_upper(Sha256::digest(&serialized));
+    let mut hasher = Sha256::new();
+    hasher.update(&serialized);
+    hasher.update(env!("CARGO_PKG_VERSION"));
+    hasher.update(ICED_TEMPLATE);
 
     let DefinitionTemp {
         module,
         glyphs: remote_glyphs,
         local_assets,
+        font,
+        codepoints,
+        iconify,
     } = definition;
 
+    if !codepoints.range.bounds().contains(&codepoints.start()) {
+        return Err(Error::Config {
+            path: path.to_path_buf(),
+            message: format!(
+                "codepoint start U+{:04X} is outside {}",
+                codepoints.start(),
+                codepoints.describe()
+            ),
+        });
+    }
+
+    let has_main = [FontFormat::Ttf, FontFormat::Otf]
+        .iter()
+        .any(|format| font.formats.contains(format));
+    if !has_main {
+        return Err(Error::Config {
+            path: path.to_path_buf(),
+            message: "font formats must include 'ttf' or 'otf'".to_string(),
+        });
+    }
+
+    if font.gradients && !font.color {
+        return Err(Error::Config {
+            path: path.to_path_buf(),
+            message: "font gradients need 'color = true'".to_string(),
+        });
+    }
+
+    check_font_weights(path, &font)?;
+
     let mut glyphs = BTreeMap::<Collection, Vec<PackIcon>>::new();
+    let mut assets = Vec::new();
     let remote_count = remote_glyphs.len();
+    let mut pinned_codepoints = HashSet::new();
 
-    for (order, (enum_var, text)) in remote_glyphs.into_iter().enumerate() {
-        let (collection, icon) = text.split_once("::").unwrap_or_else(|| {
-            panic!(
-                "glyph '{}' must use 'collection::icon' syntax (got '{}')",
-                enum_var, text
-            )
-        });
+    for (order, (enum_var, entry)) in remote_glyphs.into_iter().enumerate() {
+        let pinned = pinned_codepoint(&enum_var, &entry, &codepoints, &mut pinned_codepoints)?;
+        let options = glyph_options(&enum_var, &entry)?;
+        let text = entry.icon();
+        let Some((collection, icon)) = text.split_once("::") else {
+            return Err(Error::InvalidGlyph {
+                glyph: enum_var,
+                message: format!("must use 'collection::icon' syntax (got '{text}')"),
+            });
+        };
 
         glyphs
             .entry(Collection {
@@ -48,31 +207,62 @@ pub(crate) fn parse_definition(path: &Path, assets_path: Option<&Path>) -> (Defi
             })
             .or_default()
             .push(PackIcon {
-                enum_variant: upper_first_char(&reserved_name(enum_var)),
+                name: enum_var.clone(),
+                enum_variant: upper_first_char(&reserved_name(enum_var)?),
                 icon: icon.to_string(),
+                codepoint: 0,
+                pinned,
+                options,
                 order,
             });
     }
 
     if let Some(assets_path) = assets_path {
-        for (order, (enum_var, asset)) in local_assets.into_iter().enumerate() {
-            let asset = asset.trim();
+        for (order, (enum_var, entry)) in local_assets.into_iter().enumerate() {
+            // Keys identify glyphs in the codepoint map and ligatures
+            if glyphs.values().flatten().any(|pack| pack.name == enum_var) {
+                return Err(Error::InvalidGlyph {
+                    glyph: enum_var,
+                    message: "defined in both [glyphs] and [local_assets]".to_string(),
+                });
+            }
+
+            let pinned = pinned_codepoint(&enum_var, &entry, &codepoints, &mut pinned_codepoints)?;
+            let options = glyph_options(&enum_var, &entry)?;
+            let asset = entry.icon().trim();
             if asset.is_empty() {
-                panic!("Local asset for '{}' must not be empty", enum_var);
+                return Err(Error::InvalidGlyph {
+                    glyph: enum_var,
+                    message: "local asset name must not be empty".to_string(),
+                });
             }
 
             let asset_path = assets_path.join(asset).with_extension("svg");
-            let svg = fs::read_to_string(&asset_path).unwrap_or_else(|err| {
-                panic!(
-                    "Failed to read local asset '{}': {}",
-                    asset_path.display(),
-                    err
-                )
-            });
+            let svg = match fs::read_to_string(&asset_path) {
+                Ok(svg) => svg,
+                Err(err) => {
+                    return Err(Error::MissingAsset {
+                        glyph: enum_var,
+                        path: asset_path,
+                        message: format!("failed to read: {err}"),
+                    });
+                }
+            };
+
+            // The definition only names the asset, so its bytes must be hashed too. The
+            // name is hashed relative to the assets directory so the hash does not depend
+            // on where the checkout is
+            let asset_name = Path::new(asset).with_extension("svg");
+            hasher.update(asset_name.to_string_lossy().replace('\\', "/").as_bytes());
+            hasher.update(&svg);
 
             let svg = svg.trim();
             if svg.is_empty() {
-                panic!("Local asset '{}' is empty", asset_path.display());
+                return Err(Error::MissingAsset {
+                    glyph: enum_var,
+                    path: asset_path,
+                    message: "file is empty".to_string(),
+                });
             }
 
             glyphs
@@ -82,14 +272,27 @@ pub(crate) fn parse_definition(path: &Path, assets_path: Option<&Path>) -> (Defi
                 })
                 .or_default()
                 .push(PackIcon {
-                    enum_variant: upper_first_char(&reserved_name(enum_var)),
+                    name: enum_var.clone(),
+                    enum_variant: upper_first_char(&reserved_name(enum_var)?),
                     icon: svg.to_string(),
+                    codepoint: 0,
+                    pinned,
+                    options,
                     order: remote_count + order,
                 });
+            assets.push(asset_path);
         }
     }
 
-    let definition = Definition { module, glyphs };
+    let hash = hex_upper(hasher.finalize());
+    let definition = Definition {
+        module,
+        glyphs,
+        assets,
+        iconify,
+        font,
+        codepoints,
+    };
 
-    (definition, hash)
+    Ok((definition, hash))
 }
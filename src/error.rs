@@ -0,0 +1,164 @@
+use std::{collections::BTreeMap, fmt, io, path::PathBuf};
+
+/// Everything that can go wrong while turning a definition into a font.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The definition file could not be read or is not valid TOML.
+    Config { path: PathBuf, message: String },
+    /// A `[glyphs]` or `[local_assets]` entry is malformed.
+    InvalidGlyph { glyph: String, message: String },
+    /// A local SVG asset is missing, unreadable or empty.
+    MissingAsset {
+        glyph: String,
+        path: PathBuf,
+        message: String,
+    },
+    /// An Iconify API host is not a valid URL.
+    InvalidUrl { url: String, message: String },
+    /// Requesting a collection from Iconify failed on every host.
+    Network {
+        collection: String,
+        source: reqwest::Error,
+    },
+    /// A vendored Iconify collection file could not be read.
+    Collection {
+        collection: String,
+        path: PathBuf,
+        message: String,
+    },
+    /// Iconify answered with a different collection than requested.
+    PrefixMismatch {
+        collection: String,
+        received: String,
+    },
+    /// Offline mode is enabled and these icons are not in the cache.
+    NotCached {
+        collection: String,
+        icons: Vec<String>,
+    },
+    /// The lockfile could not be read or was edited by hand.
+    Lock { path: PathBuf, message: String },
+    /// The codepoint map is malformed or the codepoint range is exhausted.
+    Codepoints { path: PathBuf, message: String },
+    /// Requested icons that do not exist, grouped by collection.
+    MissingIcons {
+        collections: BTreeMap<String, Vec<MissingIcon>>,
+    },
+    /// The glyph SVG could not be parsed into outlines.
+    Svg { glyph: String, message: String },
+    /// Assembling the font tables failed.
+    Font {
+        glyph: Option<String>,
+        message: String,
+    },
+    /// Rendering the Iced module failed.
+    Template { message: String },
+    /// Writing generated output failed.
+    Io { path: PathBuf, source: io::Error },
+}
+
+/// An icon missing from its collection, see [`Error::MissingIcons`].
+#[derive(Debug, Clone)]
+pub struct MissingIcon {
+    pub name: String,
+    /// Similar names that do exist in the collection.
+    pub suggestions: Vec<String>,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Config { path, message } => {
+                write!(f, "invalid definition '{}': {message}", path.display())
+            }
+            Error::InvalidGlyph { glyph, message } => {
+                write!(f, "invalid glyph '{glyph}': {message}")
+            }
+            Error::MissingAsset {
+                glyph,
+                path,
+                message,
+            } => write!(
+                f,
+                "local asset '{}' for glyph '{glyph}': {message}",
+                path.display()
+            ),
+            Error::InvalidUrl { url, message } => {
+                write!(f, "invalid Iconify API url '{url}': {message}")
+            }
+            Error::Network { collection, source } => {
+                write!(
+                    f,
+                    "failed to fetch Iconify collection '{collection}': {source}"
+                )
+            }
+            Error::Collection {
+                collection,
+                path,
+                message,
+            } => write!(
+                f,
+                "failed to load Iconify collection '{collection}' from '{}': {message}",
+                path.display()
+            ),
+            Error::PrefixMismatch {
+                collection,
+                received,
+            } => write!(
+                f,
+                "Iconify prefix mismatch: requested collection '{collection}', got '{received}'"
+            ),
+            Error::NotCached { collection, icons } => write!(
+                f,
+                "offline mode: icons [{}] of collection '{collection}' are not cached",
+                icons.join(", ")
+            ),
+            Error::Lock { path, message } => {
+                write!(f, "invalid lockfile '{}': {message}", path.display())
+            }
+            Error::Codepoints { path, message } => {
+                write!(f, "invalid codepoint map '{}': {message}", path.display())
+            }
+            Error::MissingIcons { collections } => {
+                write!(f, "Iconify missing icons:")?;
+                for (collection, icons) in collections {
+                    write!(f, "\n  {collection}:")?;
+                    for (index, icon) in icons.iter().enumerate() {
+                        let separator = if index == 0 { "" } else { "," };
+                        write!(f, "{separator} '{}'", icon.name)?;
+                        if !icon.suggestions.is_empty() {
+                            write!(f, " (did you mean '{}'?)", icon.suggestions.join("', '"))?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            Error::Svg { glyph, message } => {
+                write!(f, "invalid SVG for glyph '{glyph}': {message}")
+            }
+            Error::Font {
+                glyph: Some(glyph),
+                message,
+            } => write!(f, "failed to build font glyph '{glyph}': {message}"),
+            Error::Font {
+                glyph: None,
+                message,
+            } => write!(f, "failed to build font: {message}"),
+            Error::Template { message } => write!(f, "failed to render Iced module: {message}"),
+            Error::Io { path, source } => {
+                write!(f, "failed to write '{}': {source}", path.display())
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Network { source, .. } => Some(source),
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,274 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs,
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    Error,
+    model::{CodepointConfig, CodepointRange, Collection, PackIcon},
+    utils::glyphs_in_order,
+};
+
+const CODEPOINTS_HEADER: &str =
+    "# Generated by icon; keeps the codepoint of every glyph stable across edits.\n\n";
+
+/// Contents of `icons.codepoints`, codepoints written as hex so they match the font.
+#[derive(Debug, Serialize, Deserialize)]
+struct CodepointFile {
+    /// First codepoint never handed out, so removed glyphs leave holes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    next: Option<String>,
+    #[serde(default)]
+    glyphs: BTreeMap<String, String>,
+}
+
+/// Codepoints assigned to glyph keys by previous builds.
+#[derive(Debug, Clone)]
+pub(crate) struct CodepointMap {
+    next: Option<u32>,
+    glyphs: BTreeMap<String, u32>,
+}
+
+fn parse_codepoint(value: &str) -> Option<u32> {
+    let digits = value
+        .trim()
+        .trim_start_matches("U+")
+        .trim_start_matches("0x");
+    u32::from_str_radix(digits, 16).ok()
+}
+
+impl CodepointMap {
+    /// Read the codepoint map, an absent file is an empty map.
+    pub(crate) fn load(path: &Path) -> Result<Self, Error> {
+        let map_error = |message| Error::Codepoints {
+            path: path.to_path_buf(),
+            message,
+        };
+        if !path.exists() {
+            return Ok(Self {
+                next: None,
+                glyphs: BTreeMap::new(),
+            });
+        }
+
+        let content = fs::read_to_string(path)
+            .map_err(|err| map_error(format!("failed to read file: {err}")))?;
+        let file: CodepointFile = toml::from_str(&content)
+            .map_err(|err| map_error(format!("failed to parse TOML: {err}")))?;
+
+        let mut glyphs = BTreeMap::new();
+        let mut used = HashSet::new();
+        for (name, value) in file.glyphs {
+            let codepoint = parse_codepoint(&value)
+                .filter(|codepoint| CodepointRange::is_private_use(*codepoint))
+                .ok_or_else(|| map_error(format!("invalid codepoint '{value}' for '{name}'")))?;
+            if !used.insert(codepoint) {
+                return Err(map_error(format!(
+                    "codepoint U+{codepoint:04X} is assigned twice"
+                )));
+            }
+            glyphs.insert(name, codepoint);
+        }
+
+        let next = match file.next {
+            Some(value) => Some(
+                parse_codepoint(&value)
+                    .ok_or_else(|| map_error(format!("invalid next codepoint '{value}'")))?,
+            ),
+            None => None,
+        };
+
+        Ok(Self { next, glyphs })
+    }
+
+    /// Write the codepoint map unless the file on disk already matches.
+    pub(crate) fn save(&self, path: &Path) -> Result<(), Error> {
+        let file = CodepointFile {
+            next: self.next.map(|next| format!("{next:04X}")),
+            glyphs: self
+                .glyphs
+                .iter()
+                .map(|(name, codepoint)| (name.clone(), format!("{codepoint:04X}")))
+                .collect(),
+        };
+        let serialized = toml::to_string(&file).expect("Failed to serialize codepoint map");
+        let content = format!("{CODEPOINTS_HEADER}{serialized}");
+        if fs::read_to_string(path).is_ok_and(|existing| existing == content) {
+            return Ok(());
+        }
+
+        fs::write(path, content).map_err(|source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Give every glyph its pinned or previous codepoint and new glyphs the next free one.
+    ///
+    /// Glyphs no longer in the definition are dropped without reusing their codepoints,
+    /// unless `compact` renumbers everything from the start of the range. Codepoints
+    /// outside the configured range are renumbered as well.
+    pub(crate) fn assign(
+        &mut self,
+        path: &Path,
+        glyphs: &mut BTreeMap<Collection, Vec<PackIcon>>,
+        config: &CodepointConfig,
+        compact: bool,
+    ) -> Result<(), Error> {
+        let bounds = config.range.bounds();
+        let mut previous = std::mem::take(&mut self.glyphs);
+        previous.retain(|_, codepoint| bounds.contains(codepoint));
+        if compact {
+            previous.clear();
+            self.next = None;
+        }
+
+        let mut next = self
+            .next
+            .filter(|next| (*bounds.start()..=bounds.end() + 1).contains(next))
+            .unwrap_or(config.start());
+
+        // Pinned codepoints win over anything the map handed out before
+        let pinned: HashSet<u32> = glyphs.values().flatten().filter_map(|p| p.pinned).collect();
+        // Codepoints staying with their glyph are skipped rather than moving `next` past
+        // them, so pinned or hand edited entries do not use up the range below them
+        let taken: HashSet<u32> = glyphs
+            .values()
+            .flatten()
+            .filter(|pack| pack.pinned.is_none())
+            .filter_map(|pack| previous.get(&pack.name).copied())
+            .chain(pinned.iter().copied())
+            .collect();
+
+        for (collection, index) in glyphs_in_order(glyphs) {
+            let pack = glyphs
+                .get_mut(&collection)
+                .and_then(|packs| packs.get_mut(index))
+                .ok_or_else(|| Error::Codepoints {
+                    path: path.to_path_buf(),
+                    message: format!("glyph order mismatch for collection '{}'", collection.name),
+                })?;
+
+            let kept = previous
+                .get(&pack.name)
+                .copied()
+                .filter(|codepoint| !pinned.contains(codepoint));
+            let codepoint = match pack.pinned.or(kept) {
+                Some(codepoint) => codepoint,
+                None => {
+                    while taken.contains(&next) {
+                        next += 1;
+                    }
+                    if next > config.end() {
+                        return Err(Error::Codepoints {
+                            path: path.to_path_buf(),
+                            message: format!(
+                                "no codepoint left for '{}' in {}",
+                                pack.name,
+                                config.describe()
+                            ),
+                        });
+                    }
+                    next += 1;
+                    next - 1
+                }
+            };
+
+            pack.codepoint = codepoint;
+            self.glyphs.insert(pack.name.clone(), codepoint);
+        }
+
+        self.next = Some(next);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::GlyphOptions;
+
+    fn pack(name: &str, order: usize, pinned: Option<u32>) -> PackIcon {
+        PackIcon {
+            name: name.to_string(),
+            enum_variant: name.to_string(),
+            icon: String::new(),
+            codepoint: 0,
+            pinned,
+            options: GlyphOptions::default(),
+            order,
+        }
+    }
+
+    fn definition(packs: Vec<PackIcon>) -> BTreeMap<Collection, Vec<PackIcon>> {
+        let collection = Collection {
+            name: "local".to_string(),
+            local: true,
+        };
+        BTreeMap::from([(collection, packs)])
+    }
+
+    /// Assign like a build does, the map going through its file in between.
+    fn build(
+        path: &Path,
+        packs: Vec<PackIcon>,
+        config: &CodepointConfig,
+    ) -> Result<BTreeMap<String, u32>, Error> {
+        let mut glyphs = definition(packs);
+        let mut map = CodepointMap::load(path)?;
+        map.assign(path, &mut glyphs, config, false)?;
+        map.save(path)?;
+        Ok(glyphs
+            .into_values()
+            .flatten()
+            .map(|pack| (pack.name, pack.codepoint))
+            .collect())
+    }
+
+    fn map_path(name: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "icon-codepoints-{}-{name}.codepoints",
+            std::process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn pinned_codepoints_do_not_move_the_allocator() {
+        let path = map_path("pinned");
+        let config = CodepointConfig::default();
+        for pin in [0xF8FF, 0xE0A0] {
+            let _ = fs::remove_file(&path);
+            build(&path, vec![pack("pin", 0, Some(pin))], &config).unwrap();
+            let packs = vec![pack("pin", 0, Some(pin)), pack("added", 1, None)];
+            let assigned = build(&path, packs, &config).unwrap();
+            assert_eq!(assigned["pin"], pin);
+            assert_eq!(assigned["added"], 0xE000);
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn pinned_top_of_custom_range_leaves_it_free() {
+        let path = map_path("range");
+        let config = CodepointConfig {
+            range: CodepointRange::PuaA,
+            start: Some(0xF0100),
+        };
+        build(&path, vec![pack("pin", 0, Some(0xFFFFD))], &config).unwrap();
+        for added in 1..=2 {
+            let mut packs = vec![pack("pin", 0, Some(0xFFFFD))];
+            packs.extend((1..=added).map(|order| pack(&format!("added{order}"), order, None)));
+            let assigned = build(&path, packs, &config).unwrap();
+            assert_eq!(assigned["pin"], 0xFFFFD);
+            assert_eq!(assigned["added1"], 0xF0100);
+        }
+        let assigned = build(&path, vec![pack("added2", 0, None)], &config).unwrap();
+        assert_eq!(assigned["added2"], 0xF0101);
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,381 @@
+use kurbo::{BezPath, PathEl, Point, QuadBez};
+use write_fonts::tables::glyf::Bbox;
+
+/// Standard strings take the first SIDs, custom ones are numbered after them.
+const FIRST_CUSTOM_SID: usize = 391;
+
+/// Top DICT operators.
+const OP_FONT_BBOX: u8 = 5;
+const OP_CHARSET: u8 = 15;
+const OP_CHAR_STRINGS: u8 = 17;
+const OP_PRIVATE: u8 = 18;
+
+/// Private DICT operators.
+const OP_DEFAULT_WIDTH_X: u8 = 20;
+const OP_NOMINAL_WIDTH_X: u8 = 21;
+
+/// Type 2 charstring operators.
+const OP_RLINETO: u8 = 5;
+const OP_RRCURVETO: u8 = 8;
+const OP_ENDCHAR: u8 = 14;
+const OP_RMOVETO: u8 = 21;
+
+/// A glyph of the `CFF ` table, its outline already in font units.
+pub(crate) struct CffGlyph<'a> {
+    pub outline: &'a BezPath,
+    pub advance: u16,
+}
+
+/// Write an INDEX, the CFF array of variable sized objects.
+fn index(items: &[Vec<u8>]) -> Vec<u8> {
+    let mut out = (items.len() as u16).to_be_bytes().to_vec();
+    if items.is_empty() {
+        return out;
+    }
+
+    // Offsets start at 1 and point one past the last item at the end
+    let last_offset = 1 + items.iter().map(Vec::len).sum::<usize>();
+    let off_size = match last_offset {
+        0..=0xFF => 1,
+        0x100..=0xFFFF => 2,
+        0x1_0000..=0xFF_FFFF => 3,
+        _ => 4,
+    };
+    out.push(off_size as u8);
+    let mut offset = 1;
+    for item in items.iter().map(Vec::len).chain([0]) {
+        out.extend_from_slice(&(offset as u32).to_be_bytes()[4 - off_size..]);
+        offset += item;
+    }
+    for item in items {
+        out.extend_from_slice(item);
+    }
+    out
+}
+
+/// A DICT integer operand, always in its five byte form so offsets can be patched in
+/// without moving anything.
+fn dict_int(out: &mut Vec<u8>, value: i32) {
+    out.push(29);
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+/// A charstring integer operand in its shortest form.
+///
+/// Operands are at most 16 bits, the 16.16 fixed form has no more integer bits either.
+fn charstring_int(out: &mut Vec<u8>, value: i32) -> Result<(), String> {
+    match value {
+        -107..=107 => out.push((value + 139) as u8),
+        108..=1131 => {
+            let value = value - 108;
+            out.extend_from_slice(&[(value >> 8) as u8 + 247, value as u8]);
+        }
+        -1131..=-108 => {
+            let value = -value - 108;
+            out.extend_from_slice(&[(value >> 8) as u8 + 251, value as u8]);
+        }
+        _ => {
+            let value = i16::try_from(value)
+                .map_err(|_| format!("charstring operand {value} does not fit in 16 bits"))?;
+            out.push(28);
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+    }
+    Ok(())
+}
+
+/// Encode an outline as a Type 2 charstring.
+///
+/// Points are rounded to whole font units and written relative to the previous rounded
+/// point, so rounding errors do not add up along a contour. Contours close implicitly.
+fn charstring(outline: &BezPath, advance: u16) -> Result<Vec<u8>, String> {
+    let mut out = Vec::new();
+    // The width comes first, left out when it equals the default width of zero
+    if advance != 0 {
+        charstring_int(&mut out, advance as i32)?;
+    }
+
+    let round = |point: Point| -> Result<(i32, i32), String> {
+        let (x, y) = (point.x.round(), point.y.round());
+        let range = i16::MIN as f64..=i16::MAX as f64;
+        match range.contains(&x) && range.contains(&y) {
+            true => Ok((x as i32, y as i32)),
+            false => Err(format!("point ({x}, {y}) is outside the font coordinates")),
+        }
+    };
+    let mut current = (0, 0);
+    let mut current_point = Point::ZERO;
+    let mut relative = |out: &mut Vec<u8>, point: Point| -> Result<(), String> {
+        let (x, y) = round(point)?;
+        charstring_int(out, x - current.0)?;
+        charstring_int(out, y - current.1)?;
+        current = (x, y);
+        Ok(())
+    };
+
+    for el in outline.elements() {
+        match *el {
+            PathEl::MoveTo(p) => {
+                relative(&mut out, p)?;
+                out.push(OP_RMOVETO);
+                current_point = p;
+            }
+            PathEl::LineTo(p) => {
+                relative(&mut out, p)?;
+                out.push(OP_RLINETO);
+                current_point = p;
+            }
+            PathEl::QuadTo(c, p) => {
+                let cubic = QuadBez::new(current_point, c, p).raise();
+                for point in [cubic.p1, cubic.p2, cubic.p3] {
+                    relative(&mut out, point)?;
+                }
+                out.push(OP_RRCURVETO);
+                current_point = p;
+            }
+            PathEl::CurveTo(c1, c2, p) => {
+                for point in [c1, c2, p] {
+                    relative(&mut out, point)?;
+                }
+                out.push(OP_RRCURVETO);
+                current_point = p;
+            }
+            PathEl::ClosePath => {}
+        }
+    }
+    out.push(OP_ENDCHAR);
+    Ok(out)
+}
+
+fn top_dict(bbox: Bbox, charset: usize, char_strings: usize, private: (usize, usize)) -> Vec<u8> {
+    let mut out = Vec::new();
+    for value in [bbox.x_min, bbox.y_min, bbox.x_max, bbox.y_max] {
+        dict_int(&mut out, value as i32);
+    }
+    out.push(OP_FONT_BBOX);
+    dict_int(&mut out, charset as i32);
+    out.push(OP_CHARSET);
+    dict_int(&mut out, char_strings as i32);
+    out.push(OP_CHAR_STRINGS);
+    let (size, offset) = private;
+    dict_int(&mut out, size as i32);
+    dict_int(&mut out, offset as i32);
+    out.push(OP_PRIVATE);
+    out
+}
+
+/// Build a CFF (version 1) table with one charstring per glyph, `.notdef` included.
+///
+/// Glyphs after `.notdef` are named `glyph{id}`; nothing is hinted or subroutinized.
+pub(crate) fn cff_table(
+    postscript_name: &str,
+    glyphs: &[CffGlyph],
+    bbox: Bbox,
+) -> Result<Vec<u8>, String> {
+    let char_strings = glyphs
+        .iter()
+        .map(|glyph| charstring(glyph.outline, glyph.advance))
+        .collect::<Result<Vec<_>, _>>()?;
+    let char_strings = index(&char_strings);
+
+    let names: Vec<Vec<u8>> = (1..glyphs.len())
+        .map(|id| format!("glyph{id}").into_bytes())
+        .collect();
+    if FIRST_CUSTOM_SID + names.len() > u16::MAX as usize {
+        return Err("too many glyphs for CFF glyph names".to_string());
+    }
+    let mut charset = vec![0];
+    for sid in FIRST_CUSTOM_SID..FIRST_CUSTOM_SID + names.len() {
+        charset.extend_from_slice(&(sid as u16).to_be_bytes());
+    }
+
+    let mut private = Vec::new();
+    dict_int(&mut private, 0);
+    private.push(OP_DEFAULT_WIDTH_X);
+    dict_int(&mut private, 0);
+    private.push(OP_NOMINAL_WIDTH_X);
+
+    let header = [1, 0, 4, 4];
+    let name_index = index(&[postscript_name.as_bytes().to_vec()]);
+    let string_index = index(&names);
+    let global_subrs = index(&[]);
+
+    // Operands have a fixed size, so a draft with zero offsets has the final length
+    let top_dict_len = index(&[top_dict(bbox, 0, 0, (0, 0))]).len();
+    let charset_offset =
+        header.len() + name_index.len() + top_dict_len + string_index.len() + global_subrs.len();
+    let char_strings_offset = charset_offset + charset.len();
+    let private_offset = char_strings_offset + char_strings.len();
+    let top_dict_index = index(&[top_dict(
+        bbox,
+        charset_offset,
+        char_strings_offset,
+        (private.len(), private_offset),
+    )]);
+
+    let mut out = Vec::with_capacity(private_offset + private.len());
+    out.extend_from_slice(&header);
+    for part in [
+        name_index,
+        top_dict_index,
+        string_index,
+        global_subrs,
+        charset,
+        char_strings,
+        private,
+    ] {
+        out.extend_from_slice(&part);
+    }
+    Ok(out)
+}
+
+/// Mark a font built with `CFF ` outlines as such, the font builder always writes the
+/// TrueType version tag.
+///
+/// The new tag changes the checksum of the whole font, so the adjustment stored in
+/// `head` is corrected by the same amount.
+pub(crate) fn set_cff_sfnt_version(font: &mut [u8]) {
+    const TRUETYPE: u32 = 0x0001_0000;
+    const OPEN_TYPE: &[u8; 4] = b"OTTO";
+
+    let read_u32 = |font: &[u8], at: usize| {
+        u32::from_be_bytes(font[at..at + 4].try_into().expect("four bytes"))
+    };
+    if font.len() < 12 || read_u32(font, 0) != TRUETYPE {
+        return;
+    }
+    font[..4].copy_from_slice(OPEN_TYPE);
+
+    let num_tables = u16::from_be_bytes([font[4], font[5]]) as usize;
+    let head = (0..num_tables)
+        .map(|table| 12 + table * 16)
+        .filter(|&record| record + 16 <= font.len())
+        .find(|&record| &font[record..record + 4] == b"head")
+        .map(|record| read_u32(font, record + 8) as usize);
+    // checkSumAdjustment sits 8 bytes into `head`
+    if let Some(adjustment) = head.map(|head| head + 8).filter(|at| at + 4 <= font.len()) {
+        let difference = u32::from_be_bytes(*OPEN_TYPE).wrapping_sub(TRUETYPE);
+        let value = read_u32(font, adjustment).wrapping_sub(difference);
+        font[adjustment..adjustment + 4].copy_from_slice(&value.to_be_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::{
+        read::{
+            FontData, FontRead,
+            tables::{
+                cff::Cff,
+                postscript::{
+                    Index, Index1,
+                    charstring::{CommandSink, evaluate},
+                    dict::{self, Entry},
+                },
+            },
+        },
+        types::{Fixed, GlyphId},
+    };
+
+    use super::*;
+
+    /// Charstring commands in whole font units.
+    #[derive(Debug, Default)]
+    struct Commands(Vec<(char, Vec<i32>)>);
+
+    impl CommandSink for Commands {
+        fn move_to(&mut self, x: Fixed, y: Fixed) {
+            self.0.push(('M', vec![x.to_i32(), y.to_i32()]));
+        }
+
+        fn line_to(&mut self, x: Fixed, y: Fixed) {
+            self.0.push(('L', vec![x.to_i32(), y.to_i32()]));
+        }
+
+        fn curve_to(&mut self, cx0: Fixed, cy0: Fixed, cx1: Fixed, cy1: Fixed, x: Fixed, y: Fixed) {
+            let points = [cx0, cy0, cx1, cy1, x, y];
+            self.0
+                .push(('C', points.iter().map(|v| v.to_i32()).collect()));
+        }
+
+        fn close(&mut self) {
+            self.0.push(('Z', Vec::new()));
+        }
+    }
+
+    #[test]
+    fn table_parses_back() {
+        let notdef = BezPath::new();
+        let mut outline = BezPath::new();
+        outline.move_to((100.0, -50.0));
+        outline.line_to((1200.4, -50.0));
+        outline.curve_to((1200.0, 300.0), (800.0, 700.0), (100.0, 700.0));
+        outline.close_path();
+        let glyphs = [
+            CffGlyph {
+                outline: &notdef,
+                advance: 0,
+            },
+            CffGlyph {
+                outline: &outline,
+                advance: 1300,
+            },
+        ];
+        let bbox = Bbox {
+            x_min: 100,
+            y_min: -50,
+            x_max: 1200,
+            y_max: 700,
+        };
+        let data = cff_table("IconTest", &glyphs, bbox).unwrap();
+        let cff = Cff::read(FontData::new(&data)).unwrap();
+
+        assert_eq!(cff.name(0).unwrap().to_string(), "IconTest");
+        let charset = cff.charset(0).unwrap().unwrap();
+        let sid = charset.string_id(GlyphId::new(1)).unwrap();
+        assert_eq!(cff.string(sid).unwrap().to_string(), "glyph1");
+
+        let top_dict = cff.top_dicts().get(0).unwrap();
+        let mut char_strings = None;
+        for entry in dict::entries(top_dict, None) {
+            match entry.unwrap() {
+                Entry::FontBbox(values) => {
+                    assert_eq!(values.map(Fixed::to_i32), [100, -50, 1200, 700]);
+                }
+                Entry::CharstringsOffset(offset) => char_strings = Some(offset),
+                _ => {}
+            }
+        }
+        let char_strings = Index1::read(FontData::new(&data[char_strings.unwrap()..])).unwrap();
+        assert_eq!(char_strings.count(), 2);
+
+        let mut commands = Commands::default();
+        evaluate(
+            &data,
+            char_strings.clone().into(),
+            Index::new(&[0, 0], false).unwrap(),
+            None,
+            None,
+            char_strings.get(1).unwrap(),
+            &mut commands,
+        )
+        .unwrap();
+        assert_eq!(
+            commands.0,
+            [
+                ('M', vec![100, -50]),
+                ('L', vec![1200, -50]),
+                ('C', vec![1200, 300, 800, 700, 100, 700]),
+                ('Z', vec![]),
+            ]
+        );
+    }
+
+    #[test]
+    fn oversized_deltas_are_rejected() {
+        let mut outline = BezPath::new();
+        outline.move_to((-20000.0, 0.0));
+        outline.line_to((20000.0, 0.0));
+        assert!(charstring(&outline, 0).is_err());
+    }
+}
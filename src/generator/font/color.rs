@@ -0,0 +1,483 @@
+use indexmap::{IndexMap, IndexSet};
+use kurbo::{Affine, BezPath, Point, Vec2};
+use write_fonts::{
+    tables::{
+        colr::{
+            Affine2x3, BaseGlyph, BaseGlyphList, BaseGlyphPaint, ColorLine, ColorStop, Colr,
+            Extend, Layer, LayerList, Paint,
+        },
+        cpal::{ColorRecord, Cpal},
+    },
+    types::{F2Dot14, FWord, Fixed, GlyphId16, UfWord},
+};
+
+use crate::model::PaletteColor;
+
+use super::svg::{ColorLayer, Gradient, GradientExtend, GradientShape};
+
+/// Palette index COLR uses for the text color.
+const FOREGROUND_INDEX: u16 = 0xFFFF;
+
+/// A layer glyph with its flat palette index for `COLR` version 0 and the paint it is
+/// filled with in version 1.
+struct LayerGlyph {
+    /// The regular outline followed by those of the other weights of a variable font.
+    outlines: Vec<BezPath>,
+    palette_index: u16,
+    paint: Paint,
+}
+
+/// A color glyph waiting for its layer glyphs to be added to the font.
+struct ColorGlyph {
+    base: GlyphId16,
+    advance: u16,
+    layers: Vec<LayerGlyph>,
+}
+
+/// Layers of the color glyphs, collected while the base glyphs are built and added as
+/// glyphs of their own after them.
+pub(crate) struct ColorGlyphs<'a> {
+    remap: &'a IndexMap<PaletteColor, PaletteColor>,
+    /// Write `COLR` version 1 paints so gradients survive.
+    gradients: bool,
+    palette: IndexSet<[u8; 4]>,
+    glyphs: Vec<ColorGlyph>,
+}
+
+fn fword(value: f64) -> Option<FWord> {
+    let value = value.round();
+    (i16::MIN as f64..=i16::MAX as f64)
+        .contains(&value)
+        .then(|| FWord::new(value as i16))
+}
+
+fn ufword(value: f64) -> Option<UfWord> {
+    let value = value.round();
+    (0.0..=u16::MAX as f64)
+        .contains(&value)
+        .then(|| UfWord::new(value as u16))
+}
+
+/// Whether an affine keeps circles round, turning and mirroring but not stretching.
+fn is_conformal(affine: Affine) -> bool {
+    let [a, b, c, d, _, _] = affine.as_coeffs();
+    let tolerance = 1e-6 * (a.abs() + b.abs() + c.abs() + d.abs());
+    let rotation = (a - d).abs() <= tolerance && (b + c).abs() <= tolerance;
+    let reflection = (a + d).abs() <= tolerance && (b - c).abs() <= tolerance;
+    rotation || reflection
+}
+
+impl<'a> ColorGlyphs<'a> {
+    /// `remap` replaces colors of the SVGs before they enter the palette.
+    pub(crate) fn new(remap: &'a IndexMap<PaletteColor, PaletteColor>, gradients: bool) -> Self {
+        Self {
+            remap,
+            gradients,
+            palette: IndexSet::new(),
+            glyphs: Vec::new(),
+        }
+    }
+
+    fn remapped(&self, color: PaletteColor) -> PaletteColor {
+        *self.remap.get(&color).unwrap_or(&color)
+    }
+
+    fn palette_index(&mut self, color: PaletteColor) -> Result<u16, String> {
+        match self.remapped(color) {
+            PaletteColor::Foreground => Ok(FOREGROUND_INDEX),
+            PaletteColor::Rgba(rgba) => {
+                let (index, _) = self.palette.insert_full(rgba);
+                u16::try_from(index)
+                    .ok()
+                    .filter(|index| *index < FOREGROUND_INDEX)
+                    .ok_or_else(|| "too many colors for one palette".to_string())
+            }
+        }
+    }
+
+    /// A `COLR` version 1 gradient paint, `None` when it does not fit the font
+    /// coordinates and the layer falls back to its flat color.
+    fn gradient_paint(&mut self, gradient: &Gradient) -> Result<Option<Paint>, String> {
+        let mut stops = Vec::with_capacity(gradient.stops.len());
+        for (offset, color) in &gradient.stops {
+            let index = self.palette_index(*color)?;
+            stops.push(ColorStop::new(
+                F2Dot14::from_f32(*offset),
+                index,
+                F2Dot14::ONE,
+            ));
+        }
+        let extend = match gradient.extend {
+            GradientExtend::Pad => Extend::Pad,
+            GradientExtend::Reflect => Extend::Reflect,
+            GradientExtend::Repeat => Extend::Repeat,
+        };
+        let color_line = ColorLine::new(extend, stops.len() as u16, stops);
+
+        let transform = gradient.transform;
+        let paint = match gradient.shape {
+            GradientShape::Linear { start, end } => {
+                // Colors stay constant along the perpendicular of the gradient vector,
+                // which is where the rotation point has to lie after the transform
+                let along = end - start;
+                let across = start + Vec2::new(-along.y, along.x);
+                let (p0, p1, p2) = (transform * start, transform * end, transform * across);
+                let linear = || {
+                    Some(Paint::linear_gradient(
+                        color_line,
+                        fword(p0.x)?,
+                        fword(p0.y)?,
+                        fword(p1.x)?,
+                        fword(p1.y)?,
+                        fword(p2.x)?,
+                        fword(p2.y)?,
+                    ))
+                };
+                linear()
+            }
+            GradientShape::Radial {
+                focal,
+                center,
+                radius,
+            } => {
+                let scale = transform.determinant().abs().sqrt();
+                if scale <= f64::EPSILON {
+                    return Ok(None);
+                }
+                // Circles only stay circles under a conformal transform, anything else is
+                // drawn in scaled gradient coordinates and transformed by the renderer
+                let (placement, rest) = match is_conformal(transform) {
+                    true => (transform, None),
+                    false => (
+                        Affine::scale(scale),
+                        Some(transform * Affine::scale(1.0 / scale)),
+                    ),
+                };
+                let (c0, c1): (Point, Point) = (placement * focal, placement * center);
+                let radial = || {
+                    Some(Paint::radial_gradient(
+                        color_line,
+                        fword(c0.x)?,
+                        fword(c0.y)?,
+                        UfWord::new(0),
+                        fword(c1.x)?,
+                        fword(c1.y)?,
+                        ufword(radius * scale)?,
+                    ))
+                };
+                radial().map(|paint| match rest {
+                    Some(rest) => {
+                        let [xx, yx, xy, yy, dx, dy] = rest.as_coeffs().map(Fixed::from_f64);
+                        Paint::transform(paint, Affine2x3::new(xx, yx, xy, yy, dx, dy))
+                    }
+                    None => paint,
+                })
+            }
+        };
+        Ok(paint)
+    }
+
+    /// Record the layers of a base glyph, each with its outlines already in font units: the
+    /// regular one first, then one per master of a variable font.
+    ///
+    /// Glyphs painted only in the text color are skipped, their base glyph already looks
+    /// the same and keeps following the text color.
+    pub(crate) fn add(
+        &mut self,
+        base: GlyphId16,
+        advance: u16,
+        layers: Vec<(Vec<BezPath>, &ColorLayer)>,
+    ) -> Result<(), String> {
+        let plain = layers.iter().all(|(_, layer)| {
+            let gradient = layer.gradient.as_ref().filter(|_| self.gradients);
+            gradient.is_none() && self.remapped(layer.color) == PaletteColor::Foreground
+        });
+        if plain {
+            return Ok(());
+        }
+
+        let mut layer_glyphs = Vec::with_capacity(layers.len());
+        for (outlines, layer) in layers {
+            let palette_index = self.palette_index(layer.color)?;
+            let gradient = match (&layer.gradient, self.gradients) {
+                (Some(gradient), true) => self.gradient_paint(gradient)?,
+                _ => None,
+            };
+            let paint = gradient.unwrap_or_else(|| Paint::solid(palette_index, F2Dot14::ONE));
+            layer_glyphs.push(LayerGlyph {
+                outlines,
+                palette_index,
+                paint,
+            });
+        }
+
+        self.glyphs.push(ColorGlyph {
+            base,
+            advance,
+            layers: layer_glyphs,
+        });
+        Ok(())
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.glyphs.is_empty()
+    }
+
+    /// Outlines of the layer glyphs in the order they must be added to the font, with
+    /// their advance.
+    pub(crate) fn layer_glyphs(&self) -> impl Iterator<Item = (&[BezPath], u16)> {
+        self.glyphs.iter().flat_map(|glyph| {
+            glyph
+                .layers
+                .iter()
+                .map(move |layer| (layer.outlines.as_slice(), glyph.advance))
+        })
+    }
+
+    /// Build the `COLR` and `CPAL` tables, the layer glyphs being numbered from
+    /// `first_layer`.
+    ///
+    /// The version 0 layers are always written for renderers without version 1 support;
+    /// with gradients the same layer glyphs are also painted through a version 1 graph.
+    pub(crate) fn tables(&self, first_layer: u16) -> Result<(Colr, Cpal), String> {
+        let mut base_glyphs = Vec::with_capacity(self.glyphs.len());
+        let mut base_paints = Vec::with_capacity(self.glyphs.len());
+        let mut layers = Vec::new();
+        let mut paints = Vec::new();
+        for glyph in &self.glyphs {
+            let first_layer_index =
+                u16::try_from(layers.len()).map_err(|_| "too many color layers")?;
+            let num_layers = u8::try_from(glyph.layers.len())
+                .map_err(|_| "more than 255 color layers in one glyph")?;
+            for layer in &glyph.layers {
+                let id = u16::try_from(first_layer as usize + layers.len())
+                    .map_err(|_| "too many glyphs")?;
+                layers.push(Layer::new(GlyphId16::new(id), layer.palette_index));
+                paints.push(Paint::glyph(layer.paint.clone(), GlyphId16::new(id)));
+            }
+            base_glyphs.push(BaseGlyph::new(
+                glyph.base,
+                first_layer_index,
+                num_layers as u16,
+            ));
+            base_paints.push(BaseGlyphPaint::new(
+                glyph.base,
+                Paint::colr_layers(num_layers, first_layer_index as u32),
+            ));
+        }
+        // Base glyph records are searched by glyph id
+        base_glyphs.sort_by_key(|record| record.glyph_id);
+        base_paints.sort_by_key(|record| record.glyph_id);
+
+        let num_layers = u16::try_from(layers.len()).map_err(|_| "too many color layers")?;
+        let mut colr = Colr::new(
+            base_glyphs.len() as u16,
+            Some(base_glyphs),
+            Some(layers),
+            num_layers,
+        );
+        if self.gradients {
+            colr.base_glyph_list = BaseGlyphList::new(base_paints.len() as u32, base_paints).into();
+            colr.layer_list = LayerList::new(paints.len() as u32, paints).into();
+        }
+
+        // A single palette holding every color of the SVGs
+        let records: Vec<ColorRecord> = self
+            .palette
+            .iter()
+            .map(|&[red, green, blue, alpha]| ColorRecord::new(blue, green, red, alpha))
+            .collect();
+        let num_entries = records.len() as u16;
+        let cpal = Cpal::new(num_entries, 1, num_entries, Some(records), vec![0]);
+
+        Ok((colr, cpal))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::{
+        dump_table,
+        read::{
+            FontData, FontRead,
+            tables::{
+                colr::{Colr as ReadColr, Paint as ReadPaint},
+                cpal::Cpal as ReadCpal,
+            },
+        },
+    };
+
+    use super::*;
+
+    const RED: PaletteColor = PaletteColor::Rgba([255, 0, 0, 255]);
+    const GREEN: PaletteColor = PaletteColor::Rgba([0, 255, 0, 255]);
+    const BLUE: PaletteColor = PaletteColor::Rgba([0, 0, 255, 0x80]);
+    const DARK: PaletteColor = PaletteColor::Rgba([0x10, 0x20, 0x30, 255]);
+
+    fn layer(color: PaletteColor, gradient: Option<Gradient>) -> ColorLayer {
+        ColorLayer {
+            outline: BezPath::new(),
+            color,
+            gradient,
+        }
+    }
+
+    fn gradient(shape: GradientShape, stops: Vec<(f32, PaletteColor)>) -> Option<Gradient> {
+        Some(Gradient {
+            shape,
+            stops,
+            extend: GradientExtend::Pad,
+            transform: Affine::IDENTITY,
+        })
+    }
+
+    /// Offsets and palette indices of the stops of a color line.
+    fn stops(stops: &[write_fonts::read::tables::colr::ColorStop]) -> Vec<(f32, u16)> {
+        stops
+            .iter()
+            .map(|stop| (stop.stop_offset().to_f32(), stop.palette_index()))
+            .collect()
+    }
+
+    #[test]
+    fn tables_parse_back() {
+        let remap = IndexMap::new();
+        let mut glyphs = ColorGlyphs::new(&remap, true);
+        let linear = gradient(
+            GradientShape::Linear {
+                start: Point::new(0.0, 0.0),
+                end: Point::new(100.0, 0.0),
+            },
+            vec![(0.0, GREEN), (1.0, BLUE)],
+        );
+        let radial = gradient(
+            GradientShape::Radial {
+                focal: Point::new(50.0, 50.0),
+                center: Point::new(50.0, 50.0),
+                radius: 40.0,
+            },
+            vec![(0.0, RED), (1.0, PaletteColor::Foreground)],
+        );
+        let (solid, linear, radial) = (layer(RED, None), layer(GREEN, linear), layer(RED, radial));
+        let layers = [&solid, &linear, &radial]
+            .into_iter()
+            .map(|layer| (vec![BezPath::new()], layer))
+            .collect();
+        glyphs.add(GlyphId16::new(2), 1000, layers).unwrap();
+        let dark = layer(DARK, None);
+        glyphs
+            .add(GlyphId16::new(1), 1000, vec![(vec![BezPath::new()], &dark)])
+            .unwrap();
+        // Glyphs only in the text color need no layers
+        let plain = layer(PaletteColor::Foreground, None);
+        glyphs
+            .add(
+                GlyphId16::new(3),
+                1000,
+                vec![(vec![BezPath::new()], &plain)],
+            )
+            .unwrap();
+        assert_eq!(glyphs.layer_glyphs().count(), 4);
+
+        let (colr, cpal) = glyphs.tables(10).unwrap();
+        let cpal = dump_table(&cpal).unwrap();
+        let read_cpal = ReadCpal::read(FontData::new(&cpal)).unwrap();
+        assert_eq!(read_cpal.num_palette_entries(), 4);
+        let offset = read_cpal.color_records_array_offset().offset().to_u32() as usize;
+        assert_eq!(
+            &cpal[offset..offset + 16],
+            [
+                0, 0, 255, 255, // red
+                0, 255, 0, 255, // green
+                255, 0, 0, 0x80, // translucent blue
+                0x30, 0x20, 0x10, 255,
+            ]
+        );
+
+        let colr = dump_table(&colr).unwrap();
+        let colr = ReadColr::read(FontData::new(&colr)).unwrap();
+        let base_glyphs: Vec<_> = colr
+            .base_glyph_records()
+            .unwrap()
+            .unwrap()
+            .iter()
+            .map(|base| {
+                (
+                    base.glyph_id().to_u16(),
+                    base.first_layer_index(),
+                    base.num_layers(),
+                )
+            })
+            .collect();
+        assert_eq!(base_glyphs, [(1, 3, 1), (2, 0, 3)]);
+        let layers: Vec<_> = colr
+            .layer_records()
+            .unwrap()
+            .unwrap()
+            .iter()
+            .map(|layer| (layer.glyph_id().to_u16(), layer.palette_index()))
+            .collect();
+        assert_eq!(layers, [(10, 0), (11, 1), (12, 0), (13, 3)]);
+
+        let base_list = colr.base_glyph_list().unwrap().unwrap();
+        let records = base_list.base_glyph_paint_records();
+        assert_eq!(records.len(), 2);
+        let ReadPaint::ColrLayers(first) = records[1].paint(base_list.offset_data()).unwrap()
+        else {
+            panic!("expected the layers of glyph 2");
+        };
+        assert_eq!((first.first_layer_index(), first.num_layers()), (0, 3));
+
+        let paints: Vec<_> = colr
+            .layer_list()
+            .unwrap()
+            .unwrap()
+            .paints()
+            .iter()
+            .map(|paint| match paint.unwrap() {
+                ReadPaint::Glyph(glyph) => (glyph.glyph_id().to_u16(), glyph.paint().unwrap()),
+                _ => panic!("expected a glyph paint"),
+            })
+            .collect();
+        assert_eq!(paints.len(), 4);
+
+        let ReadPaint::Solid(solid) = &paints[0].1 else {
+            panic!("expected a solid paint");
+        };
+        assert_eq!((paints[0].0, solid.palette_index()), (10, 0));
+
+        let ReadPaint::LinearGradient(linear) = &paints[1].1 else {
+            panic!("expected a linear gradient");
+        };
+        assert_eq!(paints[1].0, 11);
+        let points = [
+            linear.x0(),
+            linear.y0(),
+            linear.x1(),
+            linear.y1(),
+            linear.x2(),
+            linear.y2(),
+        ];
+        assert_eq!(points.map(|v| v.to_i16()), [0, 0, 100, 0, 0, 100]);
+        let line = linear.color_line().unwrap();
+        assert_eq!(stops(line.color_stops()), [(0.0, 1), (1.0, 2)]);
+
+        let ReadPaint::RadialGradient(radial) = &paints[2].1 else {
+            panic!("expected a radial gradient");
+        };
+        assert_eq!(paints[2].0, 12);
+        assert_eq!(
+            (
+                radial.x1().to_i16(),
+                radial.y1().to_i16(),
+                radial.radius1().to_u16()
+            ),
+            (50, 50, 40)
+        );
+        let line = radial.color_line().unwrap();
+        assert_eq!(
+            stops(line.color_stops()),
+            [(0.0, 0), (1.0, FOREGROUND_INDEX)]
+        );
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::BTreeMap;
+
+use write_fonts::{
+    tables::{
+        gsub::{Gsub, Ligature, LigatureSet, LigatureSubstFormat1, SubstitutionLookupList},
+        layout::{
+            CoverageTable, Feature, FeatureList, FeatureRecord, LangSys, Lookup, LookupFlag,
+            Script, ScriptList, ScriptRecord,
+        },
+    },
+    types::{GlyphId16, Tag},
+};
+
+/// Build a GSUB table with a single `liga` lookup replacing each component sequence
+/// with its glyph.
+pub(crate) fn ligature_gsub(ligatures: &[(Vec<GlyphId16>, GlyphId16)]) -> Gsub {
+    // Ligatures are grouped by their first component, which is what the coverage lists
+    let mut by_first = BTreeMap::<GlyphId16, Vec<Ligature>>::new();
+    for (components, glyph) in ligatures {
+        let Some((first, rest)) = components.split_first() else {
+            continue;
+        };
+        by_first
+            .entry(*first)
+            .or_default()
+            .push(Ligature::new(*glyph, rest.to_vec()));
+    }
+
+    // The first matching ligature wins, so longer names must be tried first
+    for set in by_first.values_mut() {
+        set.sort_by_key(|ligature| std::cmp::Reverse(ligature.component_glyph_ids.len()));
+    }
+
+    let coverage = CoverageTable::from(by_first.keys().copied().collect::<Vec<_>>());
+    let sets = by_first.into_values().map(LigatureSet::new).collect();
+    let subtable = LigatureSubstFormat1::new(coverage, sets);
+    let lookup = Lookup::new(LookupFlag::empty(), vec![subtable]);
+
+    let script = || Script::new(Some(LangSys::new(vec![0])), Vec::new());
+    let scripts = ScriptList::new(vec![
+        ScriptRecord::new(Tag::new(b"DFLT"), script()),
+        ScriptRecord::new(Tag::new(b"latn"), script()),
+    ]);
+    let features = FeatureList::new(vec![FeatureRecord::new(
+        Tag::new(b"liga"),
+        Feature::new(None, vec![0]),
+    )]);
+
+    Gsub::new(
+        scripts,
+        features,
+        SubstitutionLookupList::new(vec![lookup.into()]),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::{
+        dump_table,
+        read::{
+            FontData, FontRead,
+            tables::gsub::{Gsub as ReadGsub, SubstitutionLookup},
+        },
+    };
+
+    use super::*;
+
+    fn gid(id: u16) -> GlyphId16 {
+        GlyphId16::new(id)
+    }
+
+    #[test]
+    fn ligatures_parse_back_longest_first() {
+        let ligatures = [
+            (vec![gid(1), gid(2)], gid(10)),
+            (vec![gid(1), gid(2), gid(3)], gid(11)),
+            (vec![gid(4), gid(1)], gid(12)),
+            (Vec::new(), gid(13)),
+        ];
+        let bytes = dump_table(&ligature_gsub(&ligatures)).unwrap();
+        let gsub = ReadGsub::read(FontData::new(&bytes)).unwrap();
+
+        let features = gsub.feature_list().unwrap();
+        let records = features.feature_records();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].feature_tag(), Tag::new(b"liga"));
+        let scripts = gsub.script_list().unwrap();
+        let tags: Vec<_> = scripts
+            .script_records()
+            .iter()
+            .map(|r| r.script_tag())
+            .collect();
+        assert_eq!(tags, [Tag::new(b"DFLT"), Tag::new(b"latn")]);
+
+        let lookups = gsub.lookup_list().unwrap();
+        assert_eq!(lookups.lookup_count(), 1);
+        let SubstitutionLookup::Ligature(lookup) = lookups.lookups().get(0).unwrap() else {
+            panic!("expected a ligature lookup");
+        };
+        let subtable = lookup.subtables().get(0).unwrap();
+        let covered: Vec<_> = subtable.coverage().unwrap().iter().collect();
+        assert_eq!(covered, [gid(1), gid(4)]);
+
+        let sets: Vec<Vec<(GlyphId16, Vec<GlyphId16>)>> = subtable
+            .ligature_sets()
+            .iter()
+            .map(|set| {
+                set.unwrap()
+                    .ligatures()
+                    .iter()
+                    .map(|ligature| {
+                        let ligature = ligature.unwrap();
+                        let components = ligature
+                            .component_glyph_ids()
+                            .iter()
+                            .map(|id| id.get())
+                            .collect();
+                        (ligature.ligature_glyph(), components)
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(
+            sets,
+            [
+                vec![(gid(11), vec![gid(2), gid(3)]), (gid(10), vec![gid(2)])],
+                vec![(gid(12), vec![gid(1)])],
+            ]
+        );
+    }
+}
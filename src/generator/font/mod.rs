@@ -1,5 +1,13 @@
+mod cff;
+mod color;
+mod gsub;
+mod otsvg;
+mod overlap;
+mod stroke;
 mod svg;
 mod ttf;
+mod variation;
+mod woff;
 
 pub(crate) use svg::wrap_iconify_svg;
-pub(crate) use ttf::{font_path, generate_font};
+pub(crate) use ttf::{embedded_hash, font_path, generate_font, static_font_path};
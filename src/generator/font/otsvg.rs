@@ -0,0 +1,101 @@
+use write_fonts::types::GlyphId16;
+
+const HEADER_LEN: usize = 10;
+const RECORD_LEN: usize = 12;
+
+/// Build an OpenType `SVG ` table with one document per glyph, sorted by glyph id.
+pub(crate) fn svg_table(documents: &[(GlyphId16, String)]) -> Result<Vec<u8>, String> {
+    let count = u16::try_from(documents.len()).map_err(|_| "too many SVG documents")?;
+    let records_len = 2 + RECORD_LEN * documents.len();
+    let total =
+        HEADER_LEN + records_len + documents.iter().map(|(_, doc)| doc.len()).sum::<usize>();
+    let too_large = |_| "SVG documents exceed 4 GiB".to_string();
+
+    let mut out = Vec::with_capacity(total);
+    out.extend_from_slice(&0u16.to_be_bytes());
+    // The document list follows the header directly
+    out.extend_from_slice(&(HEADER_LEN as u32).to_be_bytes());
+    out.extend_from_slice(&0u32.to_be_bytes());
+
+    out.extend_from_slice(&count.to_be_bytes());
+    // Document offsets count from the start of the document list
+    let mut offset = records_len;
+    for (glyph, document) in documents {
+        out.extend_from_slice(&glyph.to_u16().to_be_bytes());
+        out.extend_from_slice(&glyph.to_u16().to_be_bytes());
+        out.extend_from_slice(&u32::try_from(offset).map_err(too_large)?.to_be_bytes());
+        out.extend_from_slice(
+            &u32::try_from(document.len())
+                .map_err(too_large)?
+                .to_be_bytes(),
+        );
+        offset += document.len();
+    }
+    for (_, document) in documents {
+        out.extend_from_slice(document.as_bytes());
+    }
+
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use kurbo::Affine;
+    use write_fonts::{
+        read::{FontData, FontRead, tables::svg::Svg},
+        types::GlyphId,
+    };
+
+    use super::*;
+    use crate::generator::font::svg::glyph_svg_document;
+
+    #[test]
+    fn table_parses_back() {
+        // The real #01FE03 must stay a color while currentColor comes back as written
+        let themed = glyph_svg_document(
+            r##"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 24"><path fill="currentColor" d="M0 0h24v12H0z"/><path fill="#01FE03" d="M0 12h24v12H0z"/></svg>"##,
+            Affine::IDENTITY,
+            3,
+        )
+        .unwrap();
+        assert_eq!(themed.matches(r#"fill="currentColor""#).count(), 1);
+        assert!(themed.to_ascii_lowercase().contains(r##"fill="#01fe03""##));
+        let plain = glyph_svg_document("M0 0h24v24H0z", Affine::IDENTITY, 5).unwrap();
+        assert!(!plain.contains("currentColor"));
+
+        let documents = [
+            (GlyphId16::new(3), themed.clone()),
+            (GlyphId16::new(5), plain.clone()),
+        ];
+        let data = svg_table(&documents).unwrap();
+        let svg = Svg::read(FontData::new(&data)).unwrap();
+        assert_eq!(svg.version(), 0);
+
+        let list = svg.svg_document_list().unwrap();
+        let records: Vec<_> = list
+            .document_records()
+            .iter()
+            .map(|record| {
+                (
+                    record.start_glyph_id().to_u16(),
+                    record.end_glyph_id().to_u16(),
+                    record.svg_doc_offset() as usize,
+                    record.svg_doc_length() as usize,
+                )
+            })
+            .collect();
+        let first = 2 + 2 * RECORD_LEN;
+        assert_eq!(
+            records,
+            [
+                (3, 3, first, themed.len()),
+                (5, 5, first + themed.len(), plain.len()),
+            ]
+        );
+
+        let document = |id: u32| svg.glyph_data(GlyphId::new(id)).unwrap();
+        assert_eq!(document(3), Some(themed.as_bytes()));
+        assert_eq!(document(5), Some(plain.as_bytes()));
+        assert_eq!(document(4), None);
+    }
+}
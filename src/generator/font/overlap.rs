@@ -0,0 +1,164 @@
+use i_overlay::{
+    core::{
+        fill_rule::FillRule, overlay::ContourDirection, overlay_rule::OverlayRule, solver::Solver,
+    },
+    float::{
+        overlay::{FloatOverlay, OverlayOptions},
+        simplify::SimplifyShape,
+    },
+    i_shape::base::data::Shapes,
+};
+use kurbo::{BezPath, PathEl};
+
+/// Maximum distance in font units between a curve and the lines replacing it.
+const FLATTEN_TOLERANCE: f64 = 0.25;
+
+type Contours = Vec<Vec<[f64; 2]>>;
+
+/// Flatten every subpath of `path` into a closed polygon.
+fn polygons(path: &BezPath, tolerance: f64) -> Contours {
+    let mut contours: Contours = Vec::new();
+    kurbo::flatten(path, tolerance, |el| match el {
+        PathEl::MoveTo(p) => contours.push(vec![[p.x, p.y]]),
+        PathEl::LineTo(p) => {
+            if let Some(contour) = contours.last_mut() {
+                contour.push([p.x, p.y]);
+            }
+        }
+        // The overlay treats every contour as closed
+        _ => {}
+    });
+    contours.retain(|contour| contour.len() >= 3);
+    contours
+}
+
+fn shapes_to_path(shapes: Shapes<[f64; 2]>) -> BezPath {
+    let mut out = BezPath::new();
+    for contour in shapes.iter().flatten() {
+        let Some((first, rest)) = contour.split_first() else {
+            continue;
+        };
+        out.move_to((first[0], first[1]));
+        for point in rest {
+            out.line_to((point[0], point[1]));
+        }
+        out.close_path();
+    }
+    out
+}
+
+fn options(output_direction: ContourDirection) -> OverlayOptions<f64> {
+    OverlayOptions {
+        output_direction,
+        ..Default::default()
+    }
+}
+
+/// Union all contours of an outline into non-overlapping ones under the nonzero rule.
+///
+/// Curves are flattened to line segments first, so the result has no off-curve points.
+/// Outer contours come out clockwise and holes counter-clockwise, as TrueType expects.
+pub(crate) fn remove_overlaps(path: &BezPath) -> BezPath {
+    let contours = polygons(path, FLATTEN_TOLERANCE);
+    let options = options(ContourDirection::Clockwise);
+    let shapes = contours.simplify_shape_custom(FillRule::NonZero, options, Solver::default());
+    shapes_to_path(shapes)
+}
+
+/// Whether any edges of the flattened contours cross or touch, other than neighbouring
+/// edges of one contour meeting at their shared point.
+pub(crate) fn has_crossings(path: &BezPath, tolerance: f64) -> bool {
+    type Edge = ([f64; 2], [f64; 2]);
+
+    let mut contours = polygons(path, tolerance);
+    // Repeated points, like an explicit line back to the start, are no edges of their own
+    for points in &mut contours {
+        points.dedup();
+        while points.len() > 1 && points.first() == points.last() {
+            points.pop();
+        }
+    }
+    contours.retain(|points| points.len() >= 3);
+    let edges: Vec<(usize, usize, usize, Edge)> = contours
+        .iter()
+        .enumerate()
+        .flat_map(|(contour, points)| {
+            (0..points.len()).map(move |index| {
+                let next = points[(index + 1) % points.len()];
+                (contour, index, points.len(), (points[index], next))
+            })
+        })
+        .collect();
+
+    let cross = |a: [f64; 2], b: [f64; 2], c: [f64; 2]| {
+        (b[0] - a[0]) * (c[1] - a[1]) - (b[1] - a[1]) * (c[0] - a[0])
+    };
+    let within = |a: [f64; 2], b: [f64; 2], p: [f64; 2]| {
+        p[0] >= a[0].min(b[0])
+            && p[0] <= a[0].max(b[0])
+            && p[1] >= a[1].min(b[1])
+            && p[1] <= a[1].max(b[1])
+    };
+    let meet = |(p1, p2): Edge, (q1, q2): Edge| {
+        let (d1, d2) = (cross(q1, q2, p1), cross(q1, q2, p2));
+        let (d3, d4) = (cross(p1, p2, q1), cross(p1, p2, q2));
+        if d1 * d2 < 0.0 && d3 * d4 < 0.0 {
+            return true;
+        }
+        (d1 == 0.0 && within(q1, q2, p1))
+            || (d2 == 0.0 && within(q1, q2, p2))
+            || (d3 == 0.0 && within(p1, p2, q1))
+            || (d4 == 0.0 && within(p1, p2, q2))
+    };
+
+    edges
+        .iter()
+        .enumerate()
+        .any(|(at, &(contour, index, len, edge))| {
+            edges[at + 1..]
+                .iter()
+                .any(|&(other, other_index, _, other_edge)| {
+                    let neighbours = contour == other
+                        && (other_index == index + 1 || (index == 0 && other_index == len - 1));
+                    !neighbours && meet(edge, other_edge)
+                })
+        })
+}
+
+/// Turn an `evenodd` outline into non-overlapping contours filling the same area under
+/// the `nonzero` rule, outer contours with a positive area like [`combine`] gives them.
+pub(crate) fn evenodd_to_nonzero(path: &BezPath, tolerance: f64) -> BezPath {
+    let contours = polygons(path, tolerance);
+    let options = options(ContourDirection::CounterClockwise);
+    let shapes = contours.simplify_shape_custom(FillRule::EvenOdd, options, Solver::default());
+    shapes_to_path(shapes)
+}
+
+/// Apply a boolean operation to two nonzero outlines still in SVG user space.
+///
+/// Outer contours of the result have a positive area, like the shapes collected from
+/// the SVG, so they keep adding to the glyph rather than cancelling out.
+fn combine(subject: &BezPath, clip: &BezPath, rule: OverlayRule, tolerance: f64) -> BezPath {
+    let subject = polygons(subject, tolerance);
+    let clip = polygons(clip, tolerance);
+    let options = options(ContourDirection::CounterClockwise);
+    let shapes =
+        FloatOverlay::with_subj_and_clip_custom(&subject, &clip, options, Solver::default())
+            .overlay(rule, FillRule::NonZero);
+    shapes_to_path(shapes)
+}
+
+/// Area covered by both outlines, see [`combine`].
+pub(crate) fn intersect(subject: &BezPath, clip: &BezPath, tolerance: f64) -> BezPath {
+    combine(subject, clip, OverlayRule::Intersect, tolerance)
+}
+
+/// Area covered by either outline, see [`combine`].
+pub(crate) fn union(subject: &BezPath, clip: &BezPath, tolerance: f64) -> BezPath {
+    combine(subject, clip, OverlayRule::Union, tolerance)
+}
+
+/// Area of `subject` not covered by `clip`, see [`combine`].
+pub(crate) fn subtract(subject: &BezPath, clip: &BezPath, tolerance: f64) -> BezPath {
+    combine(subject, clip, OverlayRule::Difference, tolerance)
+}
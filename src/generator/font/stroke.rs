@@ -0,0 +1,258 @@
+use std::f64::consts::{FRAC_PI_2, PI, TAU};
+
+use kurbo::{BezPath, PathEl, Point, Vec2};
+use usvg::{LineCap, LineJoin, Stroke};
+
+/// Segments of a round join or cap per quarter turn, whatever the width.
+const ROUND_STEPS_PER_QUARTER: f64 = 4.0;
+
+/// Joins turning less than this, in radians, are drawn as a plain corner.
+const STRAIGHT_ANGLE: f64 = 1e-6;
+
+/// Points closer than this are merged before stroking.
+const MIN_SEGMENT: f64 = 1e-9;
+
+/// The left normal of a unit direction.
+fn normal(direction: Vec2) -> Vec2 {
+    Vec2::new(-direction.y, direction.x)
+}
+
+/// `vector` turned by `angle` radians.
+fn rotate(vector: Vec2, angle: f64) -> Vec2 {
+    let (sin, cos) = angle.sin_cos();
+    Vec2::new(
+        vector.x * cos - vector.y * sin,
+        vector.x * sin + vector.y * cos,
+    )
+}
+
+/// Points of an arc around `center` starting at `from` (a unit vector), both ends
+/// included. The number of points only depends on the angle.
+fn arc(out: &mut Vec<Point>, center: Point, from: Vec2, sweep: f64, radius: f64) {
+    let steps = (sweep.abs() / FRAC_PI_2 * ROUND_STEPS_PER_QUARTER)
+        .ceil()
+        .max(1.0) as usize;
+    for step in 0..=steps {
+        let angle = sweep * step as f64 / steps as f64;
+        out.push(center + rotate(from, angle) * radius);
+    }
+}
+
+/// The corner at `pivot` on the left of a stroke turning from `incoming` to `outgoing`.
+///
+/// Which side is outside and how it is joined only depend on the directions, so every
+/// radius gives the same points. The inside goes through the pivot, which covers the
+/// corner even when the stroke is wider than the segments.
+fn join(
+    out: &mut Vec<Point>,
+    pivot: Point,
+    incoming: Vec2,
+    outgoing: Vec2,
+    radius: f64,
+    stroke: &Stroke,
+) {
+    let (before, after) = (normal(incoming), normal(outgoing));
+    let start = pivot + before * radius;
+    let end = pivot + after * radius;
+    let turn = incoming.cross(outgoing).atan2(incoming.dot(outgoing));
+
+    if turn.abs() < STRAIGHT_ANGLE {
+        out.push(start);
+        return;
+    }
+    // Turning left puts the left side inside the corner
+    if turn > 0.0 {
+        out.extend([start, pivot, end]);
+        return;
+    }
+
+    match stroke.linejoin() {
+        LineJoin::Round => arc(out, pivot, before, turn, radius),
+        LineJoin::Miter | LineJoin::MiterClip => {
+            // The miter length relative to the width only depends on the angle
+            let cos_half = ((1.0 + incoming.dot(outgoing)) / 2.0).sqrt();
+            let within_limit = cos_half * stroke.miterlimit().get() as f64 >= 1.0;
+            match within_limit {
+                true => {
+                    let miter = (before + after).normalize() * (radius / cos_half);
+                    out.extend([start, pivot + miter, end]);
+                }
+                false => out.extend([start, end]),
+            }
+        }
+        LineJoin::Bevel => out.extend([start, end]),
+    }
+}
+
+/// The cap at the end of a stroke leaving `point` along `direction`, from its left to its
+/// right side.
+fn cap(out: &mut Vec<Point>, point: Point, direction: Vec2, radius: f64, stroke: &Stroke) {
+    let side = normal(direction) * radius;
+    match stroke.linecap() {
+        LineCap::Butt => out.extend([point + side, point - side]),
+        LineCap::Square => {
+            let ahead = direction * radius;
+            out.extend([point + side + ahead, point - side + ahead]);
+        }
+        LineCap::Round => arc(out, point, normal(direction), -PI, radius),
+    }
+}
+
+/// Subpaths of a flattened path as points, without repeated points, and whether each is
+/// closed.
+fn polylines(path: &BezPath, tolerance: f64) -> Vec<(Vec<Point>, bool)> {
+    let mut polylines: Vec<(Vec<Point>, bool)> = Vec::new();
+    kurbo::flatten(path, tolerance, |el| match el {
+        PathEl::MoveTo(p) => polylines.push((vec![p], false)),
+        PathEl::LineTo(p) => {
+            if let Some((points, _)) = polylines.last_mut() {
+                points.push(p);
+            }
+        }
+        PathEl::ClosePath => {
+            if let Some((_, closed)) = polylines.last_mut() {
+                *closed = true;
+            }
+        }
+        _ => {}
+    });
+
+    for (points, closed) in &mut polylines {
+        points.dedup_by(|a, b| (*a - *b).hypot() <= MIN_SEGMENT);
+        if *closed && points.len() > 1 {
+            let first = points[0];
+            if points
+                .last()
+                .is_some_and(|last| (*last - first).hypot() <= MIN_SEGMENT)
+            {
+                points.pop();
+            }
+        }
+    }
+    polylines
+}
+
+fn push_contour(out: &mut BezPath, points: &[Point]) {
+    let Some((first, rest)) = points.split_first() else {
+        return;
+    };
+    out.move_to(*first);
+    for point in rest {
+        out.line_to(*point);
+    }
+    out.close_path();
+}
+
+/// Outline a stroke with its width scaled by `scale`, curves flattened to lines.
+///
+/// Unlike tiny-skia's stroker, which adapts its curves to the width, this one places the
+/// same number of points in the same order at any width, so outlines stroked at several
+/// scales can be interpolated. Round joins and caps are split by angle only and dashes
+/// are ignored, like the regular stroking does.
+pub(crate) fn stroke_outline(
+    centerline: &BezPath,
+    stroke: &Stroke,
+    scale: f64,
+    tolerance: f64,
+) -> BezPath {
+    let radius = stroke.width().get() as f64 * scale / 2.0;
+    let mut out = BezPath::new();
+
+    for (points, closed) in polylines(centerline, tolerance) {
+        let mut contour = Vec::new();
+        if points.len() == 1 {
+            // A zero-length subpath still shows its round or square cap
+            let center = points[0];
+            match stroke.linecap() {
+                LineCap::Butt => {}
+                LineCap::Round => {
+                    arc(&mut contour, center, Vec2::new(1.0, 0.0), TAU, radius);
+                    contour.pop();
+                }
+                LineCap::Square => contour.extend(
+                    [(1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0), (1.0, -1.0)]
+                        .map(|(x, y)| center + Vec2::new(x, y) * radius),
+                ),
+            }
+            push_contour(&mut out, &contour);
+            continue;
+        }
+
+        // Two points closed are a line there and back, whose sides would cancel out
+        let closed = closed && points.len() > 2;
+        let count = points.len();
+        let segments = if closed { count } else { count - 1 };
+        let directions: Vec<Vec2> = (0..segments)
+            .map(|index| (points[(index + 1) % count] - points[index]).normalize())
+            .collect();
+
+        if closed {
+            // The left and right sides are separate contours running against each other
+            for index in 0..count {
+                let incoming = directions[(index + count - 1) % count];
+                join(
+                    &mut contour,
+                    points[index],
+                    incoming,
+                    directions[index],
+                    radius,
+                    stroke,
+                );
+            }
+            push_contour(&mut out, &contour);
+            contour.clear();
+            for index in (0..count).rev() {
+                let incoming = -directions[index];
+                let outgoing = -directions[(index + count - 1) % count];
+                join(
+                    &mut contour,
+                    points[index],
+                    incoming,
+                    outgoing,
+                    radius,
+                    stroke,
+                );
+            }
+            push_contour(&mut out, &contour);
+            continue;
+        }
+
+        // Along the left side, around the end, back along the right side and around the
+        // start, whose last point is where the contour began
+        contour.push(points[0] + normal(directions[0]) * radius);
+        for index in 1..count - 1 {
+            let (incoming, outgoing) = (directions[index - 1], directions[index]);
+            join(
+                &mut contour,
+                points[index],
+                incoming,
+                outgoing,
+                radius,
+                stroke,
+            );
+        }
+        cap(
+            &mut contour,
+            points[count - 1],
+            directions[segments - 1],
+            radius,
+            stroke,
+        );
+        for index in (1..count - 1).rev() {
+            let (incoming, outgoing) = (-directions[index], -directions[index - 1]);
+            join(
+                &mut contour,
+                points[index],
+                incoming,
+                outgoing,
+                radius,
+                stroke,
+            );
+        }
+        cap(&mut contour, points[0], -directions[0], radius, stroke);
+        contour.pop();
+        push_contour(&mut out, &contour);
+    }
+
+    out
+}
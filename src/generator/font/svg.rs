@@ -1,28 +1,65 @@
+use crate::{
+    model::{GlyphTransform, PaletteColor, VerticalAlign},
+    utils::fmt_number,
+};
 use kurbo::{Affine, BezPath, CubicBez, PathEl, Point, Rect, Shape, Vec2};
 use usvg::{
-    Group, Node, Options, PaintOrder, Transform, Tree,
+    ClipPath, FillRule, Group, Mask, MaskType, Node, Options, Paint, PaintOrder, SpreadMethod,
+    Transform, Tree, WriteOptions,
     tiny_skia_path::{self, PathStroker},
 };
 
-pub(crate) fn wrap_iconify_svg(body: &str, width: f64, height: f64) -> String {
-    fn fmt(value: f64) -> String {
-        let mut s = format!("{value:.6}");
-        while s.contains('.') && s.ends_with('0') {
-            s.pop();
-        }
-        if s.ends_with('.') {
-            s.pop();
-        }
-        if s.is_empty() {
-            s.push('0');
-        }
-        s
-    }
+use super::{
+    overlap::{self, has_crossings, intersect, subtract, union},
+    stroke::stroke_outline,
+};
+
+/// `currentColor` resolves to the `color` property, which an injected style sheet sets to
+/// a stand-in color so foreground layers can be told apart. The first stand-in tried, the
+/// next ones only differ in green.
+const CURRENT_COLOR: [u8; 3] = [0x01, 0xFE, 0x03];
+
+/// Curves flattened for clip paths and masks stay within the icon size divided by this.
+const CLIP_TOLERANCE_DIVISOR: f64 = 4000.0;
+
+/// How strokes become outlines, each with a factor for the stroke widths.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Stroking {
+    /// Curves following the stroke like renderers draw it; `1.0` is the regular weight.
+    Scaled(f64),
+    /// Line segments with the same points at every width, so weights interpolate.
+    Interpolatable(f64),
+}
+
+/// How the shapes of an SVG become glyph outlines.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SvgOptions {
+    pub stroking: Stroking,
+    /// Orient paths for the union removing overlaps, see [`orient_nonzero`].
+    pub remove_overlaps: bool,
+    /// Tell shapes painted in `currentColor` apart, for color glyphs.
+    pub color: bool,
+}
+
+/// Settings for turning the shapes of a tree into outlines.
+#[derive(Debug, Clone, Copy)]
+struct Outlining {
+    /// Distance in canvas units curves may move when flattened.
+    tolerance: f64,
+    stroking: Stroking,
+    /// Turn paths running against the others around, see [`orient_nonzero`].
+    orient: bool,
+    /// What `currentColor` was resolved to, see [`current_color_stand_in`].
+    current_color: Option<[u8; 3]>,
+}
 
+pub(crate) fn wrap_iconify_svg(body: &str, left: f64, top: f64, width: f64, height: f64) -> String {
     format!(
-        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 {} {}">{}</svg>"#,
-        fmt(width),
-        fmt(height),
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}">{}</svg>"#,
+        fmt_number(left),
+        fmt_number(top),
+        fmt_number(width),
+        fmt_number(height),
         body.trim()
     )
 }
@@ -93,80 +130,525 @@ fn tiny_path_to_bez(path: &tiny_skia_path::Path) -> BezPath {
     bez
 }
 
-fn append_path_node(path: &usvg::Path, out: &mut BezPath) {
+/// Split a path into its subpaths, closing them like a fill does.
+fn closed_contours(path: &BezPath) -> Vec<BezPath> {
+    let mut contours: Vec<BezPath> = Vec::new();
+    for el in path.elements() {
+        if matches!(el, PathEl::MoveTo(_)) || contours.is_empty() {
+            contours.push(BezPath::new());
+        }
+        contours.last_mut().expect("contour started").push(*el);
+    }
+
+    for contour in &mut contours {
+        if !matches!(contour.elements().last(), Some(PathEl::ClosePath)) {
+            contour.close_path();
+        }
+    }
+    contours
+}
+
+/// Turn an `evenodd` path into one filling the same area under the `nonzero` rule
+/// TrueType uses.
+///
+/// Contours that do not cross are only reoriented, keeping their curves: those nested
+/// at an odd depth become holes by running against their parent. Crossing contours are
+/// resolved into non-overlapping ones, flattening the curves.
+fn evenodd_to_nonzero(path: &BezPath, tolerance: f64) -> BezPath {
+    if has_crossings(path, tolerance) {
+        return overlap::evenodd_to_nonzero(path, tolerance);
+    }
+
+    let contours = closed_contours(path);
+    let mut out = BezPath::new();
+
+    for (index, contour) in contours.iter().enumerate() {
+        let Some(PathEl::MoveTo(start)) = contour.elements().first().copied() else {
+            out.extend(contour.iter());
+            continue;
+        };
+
+        let depth = contours
+            .iter()
+            .enumerate()
+            .filter(|(other, outer)| *other != index && outer.winding(start) != 0)
+            .count();
+        let area = contour.area();
+        let reversed = match depth % 2 {
+            0 => area < 0.0,
+            _ => area > 0.0,
+        };
+
+        match reversed {
+            true => out.extend(contour.reverse_subpaths().iter()),
+            false => out.extend(contour.iter()),
+        }
+    }
+
+    out
+}
+
+/// Reverse a `nonzero` path whose contours mostly run counter to the others, so shapes
+/// mirrored by a transform add to the glyph instead of cutting holes where they overlap.
+///
+/// Only done before overlaps are removed; otherwise paths keep the direction they were
+/// drawn in, and overlapping mirrored shapes render like they always did.
+fn orient_nonzero(path: BezPath, outlining: Outlining) -> BezPath {
+    if !outlining.orient {
+        return path;
+    }
+    let area: f64 = closed_contours(&path).iter().map(|c| c.area()).sum();
+    match area < 0.0 {
+        true => path.reverse_subpaths(),
+        false => path,
+    }
+}
+
+/// Outlines a path paints, each with the paint and opacity used, in paint order.
+fn painted_outlines(path: &usvg::Path, outlining: Outlining) -> Vec<(BezPath, &Paint, f32)> {
     if !path.is_visible() {
-        return;
+        return Vec::new();
     }
 
     let ts = path.abs_transform();
     let aff = (!ts.is_identity()).then(|| transform_to_affine(ts));
 
-    let fill_path = path.fill().map(|_| {
+    let fill_path = path.fill().map(|fill| {
         let mut local = tiny_path_to_bez(path.data());
         if let Some(aff) = aff {
             local.apply_affine(aff);
         }
-        local
+        let outline = match fill.rule() {
+            FillRule::EvenOdd => evenodd_to_nonzero(&local, outlining.tolerance),
+            FillRule::NonZero => orient_nonzero(local, outlining),
+        };
+        (outline, fill.paint(), fill.opacity().get())
     });
 
     let stroke_path = path.stroke().and_then(|stroke| {
-        let res_scale = PathStroker::compute_resolution_scale(&ts);
-        let stroke = stroke.to_tiny_skia();
-
-        let stroked = path.data().stroke(&stroke, res_scale)?;
-        let mut local = tiny_path_to_bez(&stroked);
+        let mut local = match outlining.stroking {
+            // The tolerance is in canvas units, the stroke is outlined before the transform
+            Stroking::Interpolatable(scale) => {
+                let local_scale = ts.get_scale();
+                let tolerance = outlining.tolerance / local_scale.0.max(local_scale.1) as f64;
+                let centerline = tiny_path_to_bez(path.data());
+                stroke_outline(&centerline, stroke, scale, tolerance)
+            }
+            Stroking::Scaled(scale) => {
+                let mut tiny_stroke = stroke.to_tiny_skia();
+                tiny_stroke.width *= scale as f32;
+                let res_scale = PathStroker::compute_resolution_scale(&ts);
+                let stroked = path.data().stroke(&tiny_stroke, res_scale)?;
+                tiny_path_to_bez(&stroked)
+            }
+        };
         if let Some(aff) = aff {
             local.apply_affine(aff);
         }
-        Some(local)
+        Some((
+            orient_nonzero(local, outlining),
+            stroke.paint(),
+            stroke.opacity().get(),
+        ))
     });
 
-    match (fill_path, stroke_path) {
-        (Some(fill), Some(stroke)) => match path.paint_order() {
-            PaintOrder::FillAndStroke => {
-                out.extend(fill);
-                out.extend(stroke);
-            }
-            PaintOrder::StrokeAndFill => {
-                out.extend(stroke);
-                out.extend(fill);
+    let ordered = match path.paint_order() {
+        PaintOrder::FillAndStroke => [fill_path, stroke_path],
+        PaintOrder::StrokeAndFill => [stroke_path, fill_path],
+    };
+    ordered.into_iter().flatten().collect()
+}
+
+/// How a gradient continues past its first and last stop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum GradientExtend {
+    Pad,
+    Reflect,
+    Repeat,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum GradientShape {
+    Linear {
+        start: Point,
+        end: Point,
+    },
+    /// Circles growing from the focal point to the outer circle.
+    Radial {
+        focal: Point,
+        center: Point,
+        radius: f64,
+    },
+}
+
+/// A gradient in its own coordinates, `transform` maps them onto the outline.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Gradient {
+    pub shape: GradientShape,
+    /// Offsets from 0 to 1 in increasing order.
+    pub stops: Vec<(f32, PaletteColor)>,
+    pub extend: GradientExtend,
+    pub transform: Affine,
+}
+
+/// A shape of the icon together with the color it is painted in.
+#[derive(Debug, Clone)]
+pub(crate) struct ColorLayer {
+    pub outline: BezPath,
+    /// The paint as a flat color, gradients averaged over their stops.
+    pub color: PaletteColor,
+    pub gradient: Option<Gradient>,
+}
+
+/// Gradient of a paint in the coordinates of the path it fills.
+fn paint_gradient(
+    paint: &Paint,
+    opacity: f32,
+    path_transform: Affine,
+    current_color: Option<[u8; 3]>,
+) -> Option<Gradient> {
+    let (shape, base) = match paint {
+        Paint::LinearGradient(gradient) => {
+            let shape = GradientShape::Linear {
+                start: Point::new(gradient.x1() as f64, gradient.y1() as f64),
+                end: Point::new(gradient.x2() as f64, gradient.y2() as f64),
+            };
+            (shape, &***gradient)
+        }
+        Paint::RadialGradient(gradient) => {
+            let shape = GradientShape::Radial {
+                focal: Point::new(gradient.fx() as f64, gradient.fy() as f64),
+                center: Point::new(gradient.cx() as f64, gradient.cy() as f64),
+                radius: gradient.r().get() as f64,
+            };
+            (shape, &***gradient)
+        }
+        Paint::Color(_) | Paint::Pattern(_) => return None,
+    };
+
+    let stops = base
+        .stops()
+        .iter()
+        .map(|stop| {
+            let color = Paint::Color(stop.color());
+            (
+                stop.offset().get(),
+                paint_color(&color, stop.opacity().get() * opacity, current_color),
+            )
+        })
+        .collect();
+    let extend = match base.spread_method() {
+        SpreadMethod::Pad => GradientExtend::Pad,
+        SpreadMethod::Reflect => GradientExtend::Reflect,
+        SpreadMethod::Repeat => GradientExtend::Repeat,
+    };
+
+    Some(Gradient {
+        shape,
+        stops,
+        extend,
+        transform: path_transform * transform_to_affine(base.transform()),
+    })
+}
+
+/// Flat color of a paint with its opacity; gradients are averaged over their stops.
+fn paint_color(paint: &Paint, opacity: f32, current_color: Option<[u8; 3]>) -> PaletteColor {
+    let is_current =
+        |color: &usvg::Color| Some([color.red, color.green, color.blue]) == current_color;
+    let stops = match paint {
+        Paint::Color(color) if is_current(color) => {
+            return PaletteColor::Foreground;
+        }
+        Paint::Color(color) => {
+            let alpha = (opacity * 255.0).round() as u8;
+            return PaletteColor::Rgba([color.red, color.green, color.blue, alpha]);
+        }
+        Paint::LinearGradient(gradient) => gradient.stops(),
+        Paint::RadialGradient(gradient) => gradient.stops(),
+        Paint::Pattern(_) => return PaletteColor::Foreground,
+    };
+
+    // Stops in the text color cannot be averaged, the others make up the flat color
+    let colored: Vec<_> = stops
+        .iter()
+        .map(|stop| (stop.color(), stop.opacity().get()))
+        .filter(|(color, _)| !is_current(color))
+        .collect();
+    if colored.is_empty() {
+        return PaletteColor::Foreground;
+    }
+
+    let mut sum = [0.0f32; 4];
+    for (color, stop_opacity) in &colored {
+        let channels = [color.red, color.green, color.blue];
+        for (total, channel) in sum.iter_mut().zip(channels) {
+            *total += channel as f32;
+        }
+        sum[3] += stop_opacity * 255.0;
+    }
+    let count = colored.len() as f32;
+    let [r, g, b, a] = sum.map(|total| (total / count).round() as u8);
+    PaletteColor::Rgba([r, g, b, (a as f32 * opacity).round() as u8])
+}
+
+fn append_path_node(path: &usvg::Path, out: &mut Vec<ColorLayer>, outlining: Outlining) {
+    let path_transform = transform_to_affine(path.abs_transform());
+    for (outline, paint, opacity) in painted_outlines(path, outlining) {
+        out.push(ColorLayer {
+            outline,
+            color: paint_color(paint, opacity, outlining.current_color),
+            gradient: paint_gradient(paint, opacity, path_transform, outlining.current_color),
+        });
+    }
+}
+
+fn collect_group_layers(group: &Group, out: &mut Vec<ColorLayer>, outlining: Outlining) {
+    let mut content = Vec::new();
+    for node in group.children() {
+        match node {
+            Node::Group(child) => collect_group_layers(child, &mut content, outlining),
+            Node::Path(path) => append_path_node(path, &mut content, outlining),
+            _ => {}
+        }
+    }
+
+    let clip = group_clip_area(group, outlining);
+    let opacity = group.opacity().get();
+    for mut layer in content {
+        if let Some(area) = &clip {
+            layer.outline = intersect(&layer.outline, area, outlining.tolerance);
+        }
+        if let PaletteColor::Rgba([_, _, _, alpha]) = &mut layer.color {
+            *alpha = (*alpha as f32 * opacity).round() as u8;
+        }
+        let stops = layer.gradient.iter_mut().flat_map(|g| &mut g.stops);
+        for (_, color) in stops {
+            if let PaletteColor::Rgba([_, _, _, alpha]) = color {
+                *alpha = (*alpha as f32 * opacity).round() as u8;
             }
-        },
-        (Some(fill), None) => out.extend(fill),
-        (None, Some(stroke)) => out.extend(stroke),
-        (None, None) => {}
+        }
+        out.push(layer);
+    }
+}
+
+/// All outlines of a group, regardless of color.
+fn group_outline(group: &Group, outlining: Outlining) -> BezPath {
+    let mut layers = Vec::new();
+    collect_group_layers(group, &mut layers, outlining);
+    layers.into_iter().flat_map(|layer| layer.outline).collect()
+}
+
+/// Area left visible by the clip path and mask of a group, `None` when nothing is cut.
+fn group_clip_area(group: &Group, outlining: Outlining) -> Option<BezPath> {
+    let user_space = transform_to_affine(group.abs_transform());
+    let clip = group
+        .clip_path()
+        .map(|clip| clip_path_area(clip, user_space, outlining));
+    let mask = group
+        .mask()
+        .map(|mask| mask_area(mask, user_space, outlining));
+
+    match (clip, mask) {
+        (Some(clip), Some(mask)) => Some(intersect(&clip, &mask, outlining.tolerance)),
+        (clip, mask) => clip.or(mask),
+    }
+}
+
+/// Area a clip path keeps, in the coordinates of the glyph outline.
+fn clip_path_area(clip: &ClipPath, user_space: Affine, outlining: Outlining) -> BezPath {
+    let mut area = group_outline(clip.root(), outlining);
+    area.apply_affine(user_space * transform_to_affine(clip.transform()));
+
+    match clip.clip_path() {
+        Some(outer) => intersect(
+            &area,
+            &clip_path_area(outer, user_space, outlining),
+            outlining.tolerance,
+        ),
+        None => area,
     }
 }
 
-fn collect_group_paths(group: &Group, out: &mut BezPath) {
+/// Whether a paint leaves the masked content visible, treating the mask as on or off.
+fn mask_keeps(kind: MaskType, paint: &Paint, opacity: f32) -> bool {
+    let coverage = match (kind, paint) {
+        (MaskType::Luminance, Paint::Color(color)) => {
+            let luminance = 0.2125 * color.red as f32
+                + 0.7154 * color.green as f32
+                + 0.0721 * color.blue as f32;
+            opacity * luminance / 255.0
+        }
+        _ => opacity,
+    };
+    coverage >= 0.5
+}
+
+/// Paint the shapes of a mask group in order: bright or opaque shapes reveal, dark or
+/// transparent shapes painted over them hide again.
+fn paint_mask_group(group: &Group, kind: MaskType, area: &mut BezPath, outlining: Outlining) {
     for node in group.children() {
         match node {
-            Node::Group(child) => collect_group_paths(child, out),
-            Node::Path(path) => append_path_node(path, out),
+            Node::Group(child) if child.clip_path().is_none() && child.mask().is_none() => {
+                paint_mask_group(child, kind, area, outlining);
+            }
+            Node::Group(child) => {
+                let mut revealed = BezPath::new();
+                paint_mask_group(child, kind, &mut revealed, outlining);
+                if let Some(clip) = group_clip_area(child, outlining) {
+                    revealed = intersect(&revealed, &clip, outlining.tolerance);
+                }
+                *area = union(area, &revealed, outlining.tolerance);
+            }
+            Node::Path(path) => {
+                for (outline, paint, opacity) in painted_outlines(path, outlining) {
+                    *area = match mask_keeps(kind, paint, opacity) {
+                        true => union(area, &outline, outlining.tolerance),
+                        false => subtract(area, &outline, outlining.tolerance),
+                    };
+                }
+            }
             _ => {}
         }
     }
 }
 
+/// Area a mask keeps, approximated as a clip: partially transparent or gray regions are
+/// either fully kept or fully dropped.
+fn mask_area(mask: &Mask, user_space: Affine, outlining: Outlining) -> BezPath {
+    let mut area = BezPath::new();
+    paint_mask_group(mask.root(), mask.kind(), &mut area, outlining);
+    area.apply_affine(user_space);
+
+    let rect = mask.rect();
+    let mut bounds = Rect::new(
+        rect.left() as f64,
+        rect.top() as f64,
+        rect.right() as f64,
+        rect.bottom() as f64,
+    )
+    .to_path(0.0);
+    bounds.apply_affine(user_space);
+    area = intersect(&area, &bounds, outlining.tolerance);
+
+    match mask.mask() {
+        Some(outer) => intersect(
+            &area,
+            &mask_area(outer, user_space, outlining),
+            outlining.tolerance,
+        ),
+        None => area,
+    }
+}
+
 pub(crate) struct ParsedSvg {
     pub outline: BezPath,
     pub view_box: Option<Rect>,
+    /// The outline split by color in paint order, consecutive shapes of one color merged.
+    pub layers: Vec<ColorLayer>,
+    /// Every transform applied since parsing, mapping the SVG canvas onto the outline.
+    pub placement: Affine,
 }
 
-fn svg_to_bez(svg_or_d: &str) -> ParsedSvg {
-    let svg = wrap_svg_if_needed(svg_or_d);
-    let view_box = extract_view_box(&svg);
+impl ParsedSvg {
+    pub(crate) fn apply_affine(&mut self, affine: Affine) {
+        self.placement = affine * self.placement;
+        self.outline.apply_affine(affine);
+        for layer in &mut self.layers {
+            layer.outline.apply_affine(affine);
+            if let Some(gradient) = &mut layer.gradient {
+                gradient.transform = affine * gradient.transform;
+            }
+        }
+    }
+}
+
+fn hex_color([red, green, blue]: [u8; 3]) -> String {
+    format!("#{red:02x}{green:02x}{blue:02x}")
+}
 
-    let opt = Options::default();
-    let tree = Tree::from_data(svg.as_bytes(), &opt).expect("usvg parse failed");
+/// Whether the root element sets `color` as an attribute, which the style sheet setting
+/// the stand-in would override.
+fn has_root_color(svg: &str) -> bool {
+    let Some(start) = svg.find("<svg") else {
+        return false;
+    };
+    let tag = &svg[start..];
+    let tag = &tag[..tag.find('>').unwrap_or(tag.len())];
+    tag.split_whitespace()
+        .any(|attribute| attribute == "color" || attribute.starts_with("color="))
+}
 
-    let mut out = BezPath::new();
-    collect_group_paths(tree.root(), &mut out);
+/// A color for `currentColor` to resolve to that the SVG does not mention, so shapes in
+/// that color are in the text color. `None` when the SVG sets the color itself, its
+/// `currentColor` shapes keep that color.
+fn current_color_stand_in(svg: &str) -> Option<[u8; 3]> {
+    if has_root_color(svg) {
+        return None;
+    }
+    let source = svg.to_ascii_lowercase();
+    let [red, green, blue] = CURRENT_COLOR;
+    (0..=green)
+        .rev()
+        .map(|green| [red, green, blue])
+        .find(|color| !source.contains(&hex_color(*color)))
+}
 
-    ParsedSvg {
-        outline: out,
-        view_box,
+/// Parse the SVG with `currentColor` resolving to `current_color` when given.
+fn parse_tree(svg: &str, current_color: Option<[u8; 3]>) -> Result<Tree, String> {
+    let opt = Options {
+        style_sheet: current_color.map(|color| format!("svg {{ color: {} }}", hex_color(color))),
+        ..Options::default()
+    };
+    Tree::from_data(svg.as_bytes(), &opt).map_err(|err| format!("usvg parse failed: {err}"))
+}
+
+fn svg_to_bez(svg_or_d: &str, options: SvgOptions) -> Result<ParsedSvg, String> {
+    let svg = wrap_svg_if_needed(svg_or_d);
+    let current_color = options
+        .color
+        .then(|| current_color_stand_in(&svg))
+        .flatten();
+    let tree = parse_tree(&svg, current_color)?;
+
+    // usvg maps the viewBox (including a non-zero origin) onto the canvas size,
+    // so the outlines live in the canvas box rather than the raw viewBox
+    let view_box = extract_view_box(&svg).map(|_| {
+        let size = tree.size();
+        Rect::new(0.0, 0.0, size.width() as f64, size.height() as f64)
+    });
+
+    // Clipping flattens curves, finely enough to vanish once scaled to the em
+    let size = tree.size();
+    let outlining = Outlining {
+        tolerance: size.width().max(size.height()) as f64 / CLIP_TOLERANCE_DIVISOR,
+        stroking: options.stroking,
+        orient: options.remove_overlaps,
+        current_color,
+    };
+
+    let mut collected = Vec::new();
+    collect_group_layers(tree.root(), &mut collected, outlining);
+
+    let mut layers: Vec<ColorLayer> = Vec::new();
+    for layer in collected {
+        match layers.last_mut() {
+            Some(last) if last.color == layer.color && last.gradient == layer.gradient => {
+                last.outline.extend(layer.outline)
+            }
+            _ => layers.push(layer),
+        }
     }
+    let outline = layers
+        .iter()
+        .flat_map(|layer| layer.outline.iter())
+        .collect();
+
+    Ok(ParsedSvg {
+        outline,
+        view_box,
+        layers,
+        placement: Affine::IDENTITY,
+    })
 }
 
 fn bezpath_with_quadratics(path: &BezPath) -> BezPath {
@@ -213,46 +695,87 @@ fn bezpath_with_quadratics(path: &BezPath) -> BezPath {
     out
 }
 
+/// Apply the per-glyph adjustments around the center of the icon box.
+///
+/// The icon box itself only grows by the padding, so a moved or scaled outline keeps its
+/// place relative to the box instead of being fitted into the em box again.
+pub(crate) fn apply_glyph_transform(parsed_svg: &mut ParsedSvg, transform: &GlyphTransform) {
+    if transform.is_identity() {
+        return;
+    }
+
+    let frame = parsed_svg
+        .view_box
+        .unwrap_or_else(|| parsed_svg.outline.bounding_box());
+    let center = frame.center().to_vec2();
+    let flip = |flipped| if flipped { -1.0 } else { 1.0 };
+    // SVG coordinates point down, so moving up is a negative offset
+    let offset = Vec2::new(
+        transform.offset_x * frame.width(),
+        -transform.offset_y * frame.height(),
+    );
+
+    // With y pointing down a positive angle turns clockwise on screen
+    parsed_svg.apply_affine(
+        Affine::translate(-center)
+            .then_scale_non_uniform(flip(transform.flip_h), flip(transform.flip_v))
+            .then_rotate(transform.rotate.to_radians())
+            .then_scale(transform.scale)
+            .then_translate(center + offset),
+    );
+    parsed_svg.view_box = Some(frame.inflate(
+        transform.padding * frame.width(),
+        transform.padding * frame.height(),
+    ));
+}
+
+/// Scale the outline into the `em` box (default advance by ascent and descent), returning
+/// the advance width of the glyph.
+///
+/// Icons keep their aspect ratio: with `proportional` the advance follows it, otherwise
+/// the icon is fitted into the em box and centered horizontally. Icons shorter than the
+/// em box sit on its bottom edge, or in its middle with [`VerticalAlign::Center`].
 pub(crate) fn map_svg_to_em_space(
     parsed_svg: &mut ParsedSvg,
-    units_per_em: u16,
-    max_width: f64,
-    max_height: f64,
-) {
+    em: Rect,
+    proportional: bool,
+    align: VerticalAlign,
+) -> Result<f64, String> {
     const MIN_DIM: f64 = 1e-6;
 
     let svg_bbox = parsed_svg.outline.bounding_box();
-    let svg_w = svg_bbox.width();
-    let svg_h = svg_bbox.height();
-    assert!(
-        svg_w > MIN_DIM && svg_h > MIN_DIM,
-        "SVG dimensions are too small"
-    );
+    if svg_bbox.width() <= MIN_DIM || svg_bbox.height() <= MIN_DIM {
+        return Err("SVG dimensions are too small".to_string());
+    }
 
-    if let Some(vb) = parsed_svg
+    // The viewBox carries the margins the icon was drawn with, the bbox is a fallback
+    let frame = parsed_svg
         .view_box
         .filter(|r| r.width() > MIN_DIM && r.height() > MIN_DIM)
-    {
-        let scale = (units_per_em as f64) / vb.height();
-        parsed_svg.outline.apply_affine(
-            Affine::translate(Vec2::new(-vb.x0, -vb.y0))
-                .then_scale_non_uniform(scale, -scale)
-                .then_translate(Vec2::new(0.0, units_per_em as f64)),
-        );
-        return;
+        .unwrap_or(svg_bbox);
+
+    let scale = match proportional {
+        true => em.height() / frame.height(),
+        false => (em.width() / frame.width()).min(em.height() / frame.height()),
+    };
+    if !scale.is_finite() || scale <= MIN_DIM {
+        return Err("cannot scale to target box".to_string());
     }
 
-    let scale = (max_width / svg_w).min(max_height / svg_h);
-    assert!(
-        scale.is_finite() && scale > MIN_DIM,
-        "cannot scale to target box"
-    );
-
-    parsed_svg.outline.apply_affine(
-        Affine::translate(Vec2::new(-svg_bbox.x0, -svg_bbox.y0))
+    let width = frame.width() * scale;
+    let height = frame.height() * scale;
+    let advance = if proportional { width } else { em.width() };
+    let bottom = match align {
+        VerticalAlign::Baseline => em.y0,
+        VerticalAlign::Center => em.y0 + (em.height() - height) / 2.0,
+    };
+    parsed_svg.apply_affine(
+        Affine::translate(Vec2::new(-frame.x0, -frame.y0))
             .then_scale_non_uniform(scale, -scale)
-            .then_translate(Vec2::new(0.0, scale * svg_h)),
+            .then_translate(Vec2::new((advance - width) / 2.0, bottom + height)),
     );
+
+    Ok(advance)
 }
 
 fn extract_view_box(svg: &str) -> Option<Rect> {
@@ -278,8 +801,197 @@ fn extract_view_box(svg: &str) -> Option<Rect> {
     Some(Rect::new(x0, y0, x0 + w, y0 + h))
 }
 
-pub(crate) fn svg_to_quadratics(svg_or_d: &str) -> ParsedSvg {
-    let mut parsed_svg = svg_to_bez(svg_or_d);
+/// Parse the SVG keeping its cubic curves, for `CFF ` outlines.
+///
+/// Strokes are outlined as the `options` say; interpolatable ones become line segments
+/// placed the same way at every width, see [`stroke_outline`].
+pub(crate) fn svg_to_cubics(svg_or_d: &str, options: SvgOptions) -> Result<ParsedSvg, String> {
+    svg_to_bez(svg_or_d, options)
+}
+
+/// Parse the SVG into lines and quadratic curves, for `glyf` outlines; `options` as for
+/// [`svg_to_cubics`].
+pub(crate) fn svg_to_quadratics(svg_or_d: &str, options: SvgOptions) -> Result<ParsedSvg, String> {
+    let mut parsed_svg = svg_to_bez(svg_or_d, options)?;
     parsed_svg.outline = bezpath_with_quadratics(&parsed_svg.outline);
-    parsed_svg
+    for layer in &mut parsed_svg.layers {
+        layer.outline = bezpath_with_quadratics(&layer.outline);
+    }
+    Ok(parsed_svg)
+}
+
+/// Put `currentColor` back into the paints of a written document that were resolved to
+/// the `stand_in` color, leaving every other attribute and all text alone.
+fn restore_current_color(document: &str, stand_in: [u8; 3]) -> Result<String, String> {
+    const PAINTS: [&str; 3] = ["fill", "stroke", "stop-color"];
+
+    let parsed = roxmltree::Document::parse(document)
+        .map_err(|err| format!("usvg wrote an invalid document: {err}"))?;
+    let value = hex_color(stand_in);
+    let mut ranges: Vec<_> = parsed
+        .descendants()
+        .flat_map(|node| node.attributes())
+        .filter(|attribute| PAINTS.contains(&attribute.name()) && attribute.value() == value)
+        .map(|attribute| attribute.range_value())
+        .collect();
+    ranges.sort_by_key(|range| range.start);
+
+    let mut out = String::with_capacity(document.len());
+    let mut copied = 0;
+    for range in ranges {
+        out.push_str(&document[copied..range.start]);
+        out.push_str("currentColor");
+        copied = range.end;
+    }
+    out.push_str(&document[copied..]);
+    Ok(out)
+}
+
+/// The SVG of a glyph for the OpenType `SVG ` table.
+///
+/// The document is written back from the usvg tree, which leaves out scripts, event
+/// handlers, links and anything else that is not drawing. `placement` is the transform
+/// of the glyph outline; the table draws with y pointing down from the baseline.
+pub(crate) fn glyph_svg_document(
+    svg_or_d: &str,
+    placement: Affine,
+    glyph_id: u16,
+) -> Result<String, String> {
+    let svg = wrap_svg_if_needed(svg_or_d);
+    let current_color = current_color_stand_in(&svg);
+    let tree = parse_tree(&svg, current_color)?;
+    let mut written = tree.to_string(&WriteOptions::default());
+    if let Some(color) = current_color {
+        written = restore_current_color(&written, color)?;
+    }
+
+    let content = written
+        .find("<svg")
+        .and_then(|start| written[start..].find('>').map(|end| start + end + 1))
+        .zip(written.rfind("</svg>"))
+        .map(|(start, end)| &written[start..end])
+        .ok_or("usvg wrote an invalid document")?;
+
+    let [a, b, c, d, e, f] = (Affine::FLIP_Y * placement).as_coeffs().map(fmt_number);
+    Ok(format!(
+        r#"<svg xmlns="http://www.w3.org/2000/svg" xmlns:xlink="http://www.w3.org/1999/xlink"><g id="glyph{glyph_id}" transform="matrix({a} {b} {c} {d} {e} {f})">{}</g></svg>"#,
+        content.trim()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OPTIONS: SvgOptions = SvgOptions {
+        stroking: Stroking::Scaled(1.0),
+        remove_overlaps: false,
+        color: false,
+    };
+
+    /// The outline of an `evenodd` path, to be filled by the nonzero rule.
+    fn evenodd(d: &str) -> BezPath {
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100"><path fill-rule="evenodd" d="{d}"/></svg>"#
+        );
+        svg_to_bez(&svg, OPTIONS).unwrap().outline
+    }
+
+    fn filled(outline: &BezPath, x: f64, y: f64) -> bool {
+        outline.winding(Point::new(x, y)) != 0
+    }
+
+    #[test]
+    fn evenodd_nested_square_keeps_its_hole() {
+        let outline = evenodd("M10 10H90V90H10Z M30 30H70V70H30Z");
+        assert!(filled(&outline, 20.0, 20.0));
+        assert!(!filled(&outline, 50.0, 50.0));
+        assert!(!filled(&outline, 95.0, 95.0));
+
+        // Nested contours take the reorienting path, an explicit line back included
+        let nested = BezPath::from_svg("M10 10H90V90H10V10Z M30 30H70V70H30Z").unwrap();
+        assert!(!has_crossings(&nested, 0.1));
+    }
+
+    #[test]
+    fn evenodd_crossing_contours_leave_overlaps_empty() {
+        let squares = evenodd("M10 10H60V60H10Z M40 40H90V90H40Z");
+        assert!(filled(&squares, 20.0, 20.0));
+        assert!(filled(&squares, 80.0, 80.0));
+        assert!(!filled(&squares, 50.0, 50.0));
+
+        let star = evenodd("M50 5L76 90L5 35H95L24 90Z");
+        assert!(filled(&star, 50.0, 15.0));
+        assert!(filled(&star, 15.0, 38.0));
+        assert!(!filled(&star, 50.0, 50.0));
+    }
+
+    /// The outline of a 100x100 SVG with `defs`, drawing `content`.
+    fn clipped(defs: &str, content: &str) -> BezPath {
+        let svg = format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 100 100"><defs>{defs}</defs>{content}</svg>"#
+        );
+        svg_to_bez(&svg, OPTIONS).unwrap().outline
+    }
+
+    fn assert_bounds(outline: &BezPath, expected: [f64; 4]) {
+        let bounds = outline.bounding_box();
+        let actual = [bounds.x0, bounds.y0, bounds.x1, bounds.y1];
+        let close = actual
+            .iter()
+            .zip(expected)
+            .all(|(actual, expected)| (actual - expected).abs() < 0.5);
+        assert!(close, "bounds {actual:?}, expected {expected:?}");
+    }
+
+    const SQUARE: &str = r#"<rect width="100" height="100" clip-path="url(#clip)"/>"#;
+
+    #[test]
+    fn clip_paths_cut_the_outline() {
+        let rect = clipped(
+            r#"<clipPath id="clip"><rect x="20" y="30" width="40" height="20"/></clipPath>"#,
+            SQUARE,
+        );
+        assert_bounds(&rect, [20.0, 30.0, 60.0, 50.0]);
+
+        let circle = clipped(
+            r#"<clipPath id="clip"><circle cx="50" cy="50" r="20"/></clipPath>"#,
+            SQUARE,
+        );
+        assert_bounds(&circle, [30.0, 30.0, 70.0, 70.0]);
+        assert!(!filled(&circle, 33.0, 33.0));
+        assert!(filled(&circle, 50.0, 50.0));
+    }
+
+    #[test]
+    fn nested_and_transformed_clip_paths() {
+        let nested = clipped(
+            r#"<clipPath id="outer"><rect x="50" width="50" height="100"/></clipPath>
+               <clipPath id="clip" clip-path="url(#outer)"><rect x="10" y="10" width="80" height="80"/></clipPath>"#,
+            SQUARE,
+        );
+        assert_bounds(&nested, [50.0, 10.0, 90.0, 90.0]);
+
+        let moved = clipped(
+            r#"<clipPath id="clip" transform="translate(10 20)"><rect width="30" height="30"/></clipPath>"#,
+            SQUARE,
+        );
+        assert_bounds(&moved, [10.0, 20.0, 40.0, 50.0]);
+
+        // The clip path follows the transform of the element it clips
+        let scaled = clipped(
+            r#"<clipPath id="clip"><rect x="10" y="10" width="20" height="20"/></clipPath>"#,
+            r#"<g transform="scale(2)"><rect width="50" height="50" clip-path="url(#clip)"/></g>"#,
+        );
+        assert_bounds(&scaled, [20.0, 20.0, 60.0, 60.0]);
+    }
+
+    #[test]
+    fn masks_keep_what_they_reveal() {
+        let masked = clipped(
+            r#"<mask id="mask"><rect width="100" height="100" fill="black"/><rect x="25" y="10" width="50" height="30" fill="white"/></mask>"#,
+            r#"<rect width="100" height="100" mask="url(#mask)"/>"#,
+        );
+        assert_bounds(&masked, [25.0, 10.0, 75.0, 40.0]);
+    }
 }
@@ -1,15 +1,24 @@
-use std::{collections::BTreeMap, fs::File, io::Write, path};
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs, path,
+};
 
 use crate::{
-    model::{Collection, PackIcon},
-    utils::{glyphs_in_order, module_leaf},
+    Error,
+    model::{
+        BOLD_WEIGHT, Collection, FontConfig, FontFormat, FontWeight, PackIcon, REGULAR_WEIGHT,
+        VerticalAlign,
+    },
+    utils::{glyphs_in_order, module_leaf, upper_first_char},
 };
+use kurbo::{BezPath, Rect, Shape};
 use write_fonts::{
-    FontBuilder, OffsetMarker,
+    FontBuilder, OffsetMarker, dump_table,
+    read::{FontRef, TableProvider},
     tables::{
-        cmap::Cmap,
-        glyf::{GlyfLocaBuilder, Glyph, SimpleGlyph},
-        head::{Flags, Head},
+        cmap::{Cmap, Cmap4, CmapSubtable, EncodingRecord, PlatformId},
+        glyf::{Bbox, GlyfLocaBuilder, Glyph, SimpleGlyph},
+        head::{Flags, Head, MacStyle},
         hhea::Hhea,
         hmtx::Hmtx,
         loca::LocaFormat,
@@ -19,10 +28,22 @@ use write_fonts::{
         post::Post,
         vmtx::LongMetric,
     },
-    types::{FWord, Fixed, GlyphId, NameId, UfWord, Version16Dot16},
+    types::{FWord, Fixed, GlyphId, GlyphId16, NameId, Tag, UfWord, Version16Dot16},
 };
 
-use super::svg::{map_svg_to_em_space, svg_to_quadratics};
+use super::{
+    cff::{CffGlyph, cff_table, set_cff_sfnt_version},
+    color::ColorGlyphs,
+    gsub::ligature_gsub,
+    otsvg::svg_table,
+    overlap::remove_overlaps,
+    svg::{
+        Stroking, SvgOptions, apply_glyph_transform, glyph_svg_document, map_svg_to_em_space,
+        svg_to_cubics, svg_to_quadratics,
+    },
+    variation::WeightAxis,
+    woff::{woff_bytes, woff2_bytes},
+};
 
 fn make_postscript_name(base: &str) -> String {
     base.chars()
@@ -36,84 +57,451 @@ fn make_postscript_name(base: &str) -> String {
 pub(crate) fn font_path(
     path_hint: impl AsRef<path::Path>,
     module_path: impl AsRef<path::Path>,
+    format: FontFormat,
 ) -> (path::PathBuf, String) {
     let module_input = module_path.as_ref().to_string_lossy();
     let module_name = module_leaf(&module_input);
 
     let out_path = path_hint
         .as_ref()
-        .with_file_name(format!("{module_name}.ttf"));
+        .with_file_name(format!("{module_name}.{}", format.extension()));
 
     (out_path, module_name)
 }
 
+/// Path of the font of a static weight, "{module}-{weight}" next to the regular font.
+pub(crate) fn static_font_path(
+    path_hint: impl AsRef<path::Path>,
+    module_path: impl AsRef<path::Path>,
+    weight: &str,
+    format: FontFormat,
+) -> path::PathBuf {
+    let (regular_path, module_name) = font_path(path_hint, module_path, format);
+    regular_path.with_file_name(format!("{module_name}-{weight}.{}", format.extension()))
+}
+
+/// `OS/2` Unicode range bits 57 (non-plane 0) and 60 (Private Use Area).
+fn unicode_range_2(codepoints: &[(char, GlyphId)]) -> u32 {
+    let mut bits = 0;
+    for (ch, _) in codepoints {
+        match *ch as u32 {
+            0xE000..=0xF8FF => bits |= 1 << (60 - 32),
+            0x10000.. => bits |= 1 << (57 - 32),
+            _ => (),
+        }
+    }
+    bits
+}
+
+/// `OS/2` Unicode range bit 90, the Supplementary Private Use Areas.
+fn unicode_range_3(codepoints: &[(char, GlyphId)]) -> u32 {
+    match codepoints.iter().any(|(ch, _)| *ch as u32 >= 0xF0000) {
+        true => 1 << (90 - 64),
+        false => 0,
+    }
+}
+
+/// Fonts with only supplementary plane codepoints still need the BMP subtables, which
+/// some platforms refuse to load a font without; they only map the 0xFFFF sentinel.
+fn add_empty_bmp_subtables(cmap: &mut Cmap) {
+    let has_bmp = cmap
+        .encoding_records
+        .iter()
+        .any(|record| record.platform_id == PlatformId::Windows && record.encoding_id == 1);
+    if has_bmp {
+        return;
+    }
+
+    let subtable = CmapSubtable::Format4(Cmap4::new(
+        0,
+        vec![0xFFFF],
+        vec![0xFFFF],
+        vec![1],
+        vec![0],
+        Vec::new(),
+    ));
+    cmap.encoding_records.extend([
+        EncodingRecord::new(PlatformId::Unicode, 3, subtable.clone()),
+        EncodingRecord::new(PlatformId::Windows, 1, subtable),
+    ]);
+    cmap.encoding_records
+        .sort_by_key(|record| (record.platform_id, record.encoding_id));
+}
+
+/// Glyph outlines in the flavor of the font being built.
+enum Outlines {
+    /// Quadratic outlines of the `glyf` table.
+    TrueType(GlyfLocaBuilder),
+    /// Cubic outlines written to the `CFF ` table once every glyph is known.
+    Cff(Vec<BezPath>),
+}
+
+impl Outlines {
+    /// Add the outline of the next glyph, returning its bounding box.
+    fn add(&mut self, outline: &BezPath) -> Result<Bbox, String> {
+        match self {
+            Outlines::TrueType(_) => {
+                let glyph = SimpleGlyph::from_bezpath(outline)
+                    .map_err(|err| format!("malformed outline: {err:?}"))?;
+                self.add_glyph(&glyph)
+            }
+            Outlines::Cff(outlines) => {
+                let bounds = outline.bounding_box();
+                let coordinate = |value: f64| value.clamp(i16::MIN as f64, i16::MAX as f64) as i16;
+                outlines.push(outline.clone());
+                Ok(Bbox {
+                    x_min: coordinate(bounds.x0.floor()),
+                    y_min: coordinate(bounds.y0.floor()),
+                    x_max: coordinate(bounds.x1.ceil()),
+                    y_max: coordinate(bounds.y1.ceil()),
+                })
+            }
+        }
+    }
+
+    /// Add a `glyf` glyph built elsewhere, such as the regular glyph of a variable font.
+    fn add_glyph(&mut self, glyph: &SimpleGlyph) -> Result<Bbox, String> {
+        match self {
+            Outlines::TrueType(builder) => {
+                builder.add_glyph(glyph).map_err(|err| err.to_string())?;
+                Ok(glyph.bbox)
+            }
+            Outlines::Cff(_) => Err("TrueType glyph in a CFF font".to_string()),
+        }
+    }
+
+    fn add_empty(&mut self) -> Result<(), String> {
+        match self {
+            Outlines::TrueType(builder) => builder
+                .add_glyph(&Glyph::Empty)
+                .map(|_| ())
+                .map_err(|err| err.to_string()),
+            Outlines::Cff(outlines) => {
+                outlines.push(BezPath::new());
+                Ok(())
+            }
+        }
+    }
+}
+
+/// The glyph id after `gid`, failing once a font would have more glyphs than ids.
+fn next_glyph_id(gid: u16, glyph: Option<&str>) -> Result<u16, Error> {
+    gid.checked_add(1).ok_or_else(|| Error::Font {
+        glyph: glyph.map(str::to_string),
+        message: format!("a font holds at most {} glyphs", u16::MAX),
+    })
+}
+
+/// Build the font tables with `glyf` outlines, or `CFF ` ones for [`FontFormat::Otf`].
+///
+/// `weight` names a static weight to draw instead of the regular one of the SVGs.
 pub(crate) fn generate_font_bytes(
     module_name: &str,
-    glyphs: &mut BTreeMap<Collection, Vec<PackIcon>>,
-) -> Vec<u8> {
+    hash: &str,
+    font: &FontConfig,
+    glyphs: &BTreeMap<Collection, Vec<PackIcon>>,
+    format: FontFormat,
+    weight: Option<(&str, &FontWeight)>,
+) -> Result<Vec<u8>, Error> {
     let units_per_em: u16 = 1000;
-    let ascent: i16 = units_per_em as i16;
-    let descent: i16 = 0;
+    let (ascent, descent): (i16, i16) = match font.align {
+        VerticalAlign::Baseline => (1000, 0),
+        VerticalAlign::Center => (800, -200),
+    };
     let advance_width: u16 = 1000;
-    let max_width = advance_width as f64;
-    let max_height = (ascent - descent) as f64;
+    let em = Rect::new(0.0, descent as f64, advance_width as f64, ascent as f64);
 
-    let mut gl = GlyfLocaBuilder::new();
-    gl.add_glyph(&Glyph::Empty).expect(".notdef");
+    let cff = format == FontFormat::Otf;
+    let mut outlines = match cff {
+        true => Outlines::Cff(Vec::new()),
+        false => Outlines::TrueType(GlyfLocaBuilder::new()),
+    };
+    outlines.add_empty().expect(".notdef");
+    // Only TrueType outlines vary, the OTF keeps the regular weight
+    let mut axis = (font.variable && !cff).then(|| WeightAxis::new(&font.weights));
+    let svg_options = SvgOptions {
+        stroking: match axis {
+            Some(_) => Stroking::Interpolatable(1.0),
+            None => Stroking::Scaled(1.0),
+        },
+        remove_overlaps: font.remove_overlaps,
+        color: font.color,
+    };
 
-    // Private Area from 0xE000 to 0xF8FF
-    let mut next_codepoint: u16 = 0xE000;
     let mut next_gid: u16 = 1;
     let mut codepoints: Vec<(char, GlyphId)> = Vec::new();
+    let mut metrics = vec![LongMetric {
+        advance: advance_width,
+        side_bearing: 0,
+    }];
+    let mut names: Vec<(String, GlyphId16)> = Vec::new();
+    let mut bboxes: Vec<(Bbox, u16)> = Vec::new();
+    let mut color_glyphs = ColorGlyphs::new(&font.palette, font.gradients);
+    let mut svg_documents = Vec::new();
 
     let ordered_entries = glyphs_in_order(glyphs);
     for (collection, index) in ordered_entries {
         let pack = glyphs
-            .get_mut(&collection)
-            .and_then(|packs| packs.get_mut(index))
-            .unwrap_or_else(|| panic!("glyph order mismatch for collection '{}'", collection.name));
+            .get(&collection)
+            .and_then(|packs| packs.get(index))
+            .ok_or_else(|| Error::Font {
+                glyph: None,
+                message: format!("glyph order mismatch for collection '{}'", collection.name),
+            })?;
 
         if pack.icon.trim().is_empty() {
-            panic!("{} svg should not be empty", pack.enum_variant)
+            return Err(Error::Svg {
+                glyph: pack.enum_variant.clone(),
+                message: "svg should not be empty".to_string(),
+            });
         }
 
-        let mut parsed_svg = svg_to_quadratics(&pack.icon);
-
-        map_svg_to_em_space(&mut parsed_svg, units_per_em, max_width, max_height);
-
-        let mut sg = SimpleGlyph::from_bezpath(&parsed_svg.outline).expect("malformed outline");
+        let svg_error = |message| Error::Svg {
+            glyph: pack.enum_variant.clone(),
+            message,
+        };
+        let parse = match cff {
+            true => svg_to_cubics,
+            false => svg_to_quadratics,
+        };
+        let mut parsed_svg = parse(&pack.icon, svg_options).map_err(svg_error)?;
+        apply_glyph_transform(&mut parsed_svg, &pack.options.transform);
+
+        let proportional = pack.options.proportional.unwrap_or(font.proportional);
+        let advance = map_svg_to_em_space(&mut parsed_svg, em, proportional, font.align)
+            .map_err(svg_error)?;
+
+        // Other weights are placed like the regular outline, whatever their stroked bounds
+        if let Some((_, weight)) = weight {
+            let placement = parsed_svg.placement;
+            let options = SvgOptions {
+                stroking: Stroking::Scaled(weight.stroke),
+                ..svg_options
+            };
+            parsed_svg = parse(&pack.icon, options).map_err(svg_error)?;
+            parsed_svg.apply_affine(placement);
+        }
+        let mut masters = Vec::new();
+        for scale in axis.iter().flat_map(|axis| axis.stroke_scales()) {
+            let options = SvgOptions {
+                stroking: Stroking::Interpolatable(scale),
+                ..svg_options
+            };
+            let mut master = parse(&pack.icon, options).map_err(svg_error)?;
+            master.apply_affine(parsed_svg.placement);
+            masters.push(master);
+        }
 
-        // Without the two lines below the glyph would be centered at the left middle
-        sg.bbox.x_min = 0;
-        sg.bbox.y_min = 0;
+        let font_error = |err: &dyn std::fmt::Display| Error::Font {
+            glyph: Some(pack.enum_variant.clone()),
+            message: err.to_string(),
+        };
+        if font.remove_overlaps {
+            parsed_svg.outline = remove_overlaps(&parsed_svg.outline);
+            for layer in &mut parsed_svg.layers {
+                layer.outline = remove_overlaps(&layer.outline);
+            }
+        }
 
-        gl.add_glyph(&sg).expect("add glyph");
+        let bbox = match &mut axis {
+            Some(axis) => {
+                let outlines_at_weights: Vec<BezPath> = masters
+                    .iter()
+                    .map(|master| master.outline.clone())
+                    .collect();
+                axis.add(
+                    GlyphId16::new(next_gid),
+                    &parsed_svg.outline,
+                    &outlines_at_weights,
+                )
+                .and_then(|glyph| outlines.add_glyph(&glyph))
+            }
+            None => outlines.add(&parsed_svg.outline),
+        }
+        .map_err(|err| font_error(&err))?;
 
-        let ch = char::from_u32(next_codepoint as u32).expect("valid PUA codepoint");
+        let ch = char::from_u32(pack.codepoint).expect("valid PUA codepoint");
         let gid = GlyphId::from(next_gid);
         codepoints.push((ch, gid));
+        let advance = advance.round() as u16;
+        metrics.push(LongMetric {
+            advance,
+            side_bearing: bbox.x_min,
+        });
+        if font.color {
+            let layers = parsed_svg
+                .layers
+                .iter()
+                .enumerate()
+                .filter(|(_, layer)| !layer.outline.elements().is_empty())
+                .map(|(index, layer)| {
+                    let at_weights = masters.iter().map(|master| {
+                        let outline = master.layers.get(index).map(|l| l.outline.clone());
+                        outline.unwrap_or_default()
+                    });
+                    let outlines = std::iter::once(layer.outline.clone())
+                        .chain(at_weights)
+                        .collect();
+                    (outlines, layer)
+                })
+                .collect();
+            color_glyphs
+                .add(GlyphId16::new(next_gid), advance, layers)
+                .map_err(|err| font_error(&err))?;
+        }
+        bboxes.push((bbox, advance));
+        if font.ligatures {
+            names.push((pack.name.clone(), GlyphId16::new(next_gid)));
+        }
 
-        pack.icon = ch.to_string();
+        // The documents would show the strokes of the SVGs, not those of the weight
+        if font.svg && weight.is_none() {
+            let document = glyph_svg_document(&pack.icon, parsed_svg.placement, next_gid)
+                .map_err(svg_error)?;
+            svg_documents.push((GlyphId16::new(next_gid), document));
+        }
 
-        next_codepoint = next_codepoint.wrapping_add(1);
-        next_gid = next_gid.wrapping_add(1);
+        next_gid = next_glyph_id(next_gid, Some(&pack.enum_variant))?;
+    }
+
+    // Every character of a name needs a glyph to be substituted; they are left empty
+    // and without advance so only the icon shows. Without `liga` applied those letters
+    // render as nothing, which the README warns about
+    let mut ligatures = Vec::with_capacity(names.len());
+    let mut components = BTreeMap::<char, GlyphId16>::new();
+    let icon_chars: HashSet<char> = codepoints.iter().map(|(ch, _)| *ch).collect();
+    for (name, glyph) in names {
+        let mut sequence = Vec::with_capacity(name.len());
+        for ch in name.chars() {
+            if let Some(component) = components.get(&ch) {
+                sequence.push(*component);
+                continue;
+            }
+            if icon_chars.contains(&ch) {
+                return Err(Error::Font {
+                    glyph: Some(name),
+                    message: format!(
+                        "ligature character U+{:04X} is an icon codepoint",
+                        ch as u32
+                    ),
+                });
+            }
+
+            outlines.add_empty().map_err(|message| Error::Font {
+                glyph: Some(name.clone()),
+                message,
+            })?;
+            let component = GlyphId16::new(next_gid);
+            codepoints.push((ch, GlyphId::from(next_gid)));
+            metrics.push(LongMetric {
+                advance: 0,
+                side_bearing: 0,
+            });
+            components.insert(ch, component);
+            sequence.push(component);
+            next_gid = next_glyph_id(next_gid, Some(&name))?;
+        }
+        ligatures.push((sequence, glyph));
+    }
+
+    // Color layers are glyphs without a codepoint, placed after everything else
+    let first_layer = next_gid;
+    for (layer, advance) in color_glyphs.layer_glyphs() {
+        let bbox = match &mut axis {
+            Some(axis) => axis
+                .add(GlyphId16::new(next_gid), &layer[0], &layer[1..])
+                .and_then(|glyph| outlines.add_glyph(&glyph)),
+            None => outlines.add(&layer[0]),
+        }
+        .map_err(|message| Error::Font {
+            glyph: None,
+            message: format!("color layer: {message}"),
+        })?;
+        metrics.push(LongMetric {
+            advance,
+            side_bearing: bbox.x_min,
+        });
+        next_gid = next_glyph_id(next_gid, None)?;
     }
 
     let total_glyphs = next_gid;
-    let (glyf, loca, loca_fmt) = gl.build();
-    let index_to_loc_format: i16 = match loca_fmt {
-        LocaFormat::Short => 0,
-        LocaFormat::Long => 1,
+
+    let advance_width_max = metrics.iter().map(|m| m.advance).max().unwrap_or(0);
+    // OS/2 averages over the glyphs that take up space
+    let widths: Vec<u32> = metrics
+        .iter()
+        .map(|m| m.advance as u32)
+        .filter(|&advance| advance > 0)
+        .collect();
+    let avg_char_width = (widths.iter().sum::<u32>() / widths.len().max(1) as u32) as i16;
+
+    // Font-wide extents cover the outlines; empty glyphs do not count
+    let font_bbox = bboxes
+        .iter()
+        .map(|(bbox, _)| *bbox)
+        .reduce(|a, b| a.union(b))
+        .unwrap_or(Bbox {
+            x_min: 0,
+            y_min: descent,
+            x_max: advance_width as i16,
+            y_max: ascent,
+        });
+    let min_left_side_bearing = bboxes.iter().map(|(bbox, _)| bbox.x_min).min();
+    let min_right_side_bearing = bboxes
+        .iter()
+        .map(|(bbox, advance)| *advance as i16 - bbox.x_max)
+        .min();
+    let x_max_extent = bboxes.iter().map(|(bbox, _)| bbox.x_max).max();
+
+    let font_error = |message: String| Error::Font {
+        glyph: None,
+        message,
+    };
+    let (weight_class, subfam) = match weight {
+        Some((name, weight)) => (weight.weight, upper_first_char(name)),
+        None => (REGULAR_WEIGHT, "Regular".to_string()),
+    };
+    let bold = weight_class == BOLD_WEIGHT;
+    let ps_subfam: String = subfam.chars().filter(char::is_ascii_alphanumeric).collect();
+    let ps = format!("{}-{}", make_postscript_name(module_name), ps_subfam);
+    let (index_to_loc_format, outline_tables) = match outlines {
+        Outlines::TrueType(gl) => {
+            let (glyf, loca, loca_fmt) = gl.build();
+            let index_to_loc_format: i16 = match loca_fmt {
+                LocaFormat::Short => 0,
+                LocaFormat::Long => 1,
+            };
+            let glyf = dump_table(&glyf).map_err(|err| font_error(err.to_string()))?;
+            let loca = dump_table(&loca).map_err(|err| font_error(err.to_string()))?;
+            let tables = vec![(Tag::new(b"glyf"), glyf), (Tag::new(b"loca"), loca)];
+            (index_to_loc_format, tables)
+        }
+        Outlines::Cff(outlines) => {
+            let glyphs: Vec<CffGlyph> = outlines
+                .iter()
+                .zip(&metrics)
+                .map(|(outline, metric)| CffGlyph {
+                    outline,
+                    advance: metric.advance,
+                })
+                .collect();
+            let table = cff_table(&ps, &glyphs, font_bbox).map_err(font_error)?;
+            (0, vec![(Tag::new(b"CFF "), table)])
+        }
     };
 
     let head = Head {
         font_revision: Fixed::ONE,
         flags: Flags::empty(),
         units_per_em,
-        x_min: 0,
-        y_min: descent,
-        x_max: units_per_em as i16,
-        y_max: ascent,
+        x_min: font_bbox.x_min,
+        y_min: font_bbox.y_min,
+        x_max: font_bbox.x_max,
+        y_max: font_bbox.y_max,
+        mac_style: match bold {
+            true => MacStyle::BOLD,
+            false => MacStyle::empty(),
+        },
         lowest_rec_ppem: 8,
         index_to_loc_format,
         ..Default::default()
@@ -123,8 +511,10 @@ pub(crate) fn generate_font_bytes(
         ascender: FWord::from(ascent),
         descender: FWord::from(descent),
         line_gap: FWord::from(0),
-        advance_width_max: UfWord::from(advance_width),
-        x_max_extent: FWord::from(advance_width as i16),
+        advance_width_max: UfWord::from(advance_width_max),
+        min_left_side_bearing: FWord::from(min_left_side_bearing.unwrap_or(0)),
+        min_right_side_bearing: FWord::from(min_right_side_bearing.unwrap_or(0)),
+        x_max_extent: FWord::from(x_max_extent.unwrap_or(0)),
         number_of_h_metrics: total_glyphs.max(1),
         ..Default::default()
     };
@@ -134,14 +524,7 @@ pub(crate) fn generate_font_bytes(
         ..Default::default()
     };
 
-    let mut long_metrics = Vec::with_capacity(hhea.number_of_h_metrics as usize);
-    for _ in 0..hhea.number_of_h_metrics {
-        long_metrics.push(LongMetric {
-            advance: advance_width,
-            side_bearing: 0,
-        });
-    }
-    let hmtx = Hmtx::new(long_metrics, Vec::new());
+    let hmtx = Hmtx::new(metrics, Vec::new());
 
     let mut post = Post::new(
         Fixed::from_f64(0.0),
@@ -155,17 +538,26 @@ pub(crate) fn generate_font_bytes(
     );
     post.version = Version16Dot16::VERSION_3_0;
 
+    let variation = axis
+        .map(|axis| axis.tables(total_glyphs))
+        .transpose()
+        .map_err(font_error)?;
+
     let name = {
         let notice = "Contains third-party icons under their original licenses.";
         let family = module_name.to_string();
-        let subfam = "Regular";
         let full = format!("{family} {subfam}");
-        let ps = format!("{}-{}", make_postscript_name(module_name), subfam);
+        // Older apps group at most a regular and a bold per family, every other weight
+        // is the regular of a family of its own there
+        let (legacy_family, legacy_subfam) = match weight_class {
+            REGULAR_WEIGHT | BOLD_WEIGHT => (family.clone(), subfam.clone()),
+            _ => (full.clone(), "Regular".to_string()),
+        };
         let vers = "Version 1.000".to_string();
         let desc = "Auto generated icon collection".to_string();
         let vend = "https://github.com/iMohmmedSA".to_string();
 
-        let recs = vec![
+        let mut recs = vec![
             NameRecord::new(
                 3,
                 1,
@@ -178,14 +570,21 @@ pub(crate) fn generate_font_bytes(
                 1,
                 0x0409,
                 NameId::FAMILY_NAME,
-                OffsetMarker::new(family.clone()),
+                OffsetMarker::new(legacy_family),
             ),
             NameRecord::new(
                 3,
                 1,
                 0x0409,
                 NameId::SUBFAMILY_NAME,
-                OffsetMarker::new(subfam.to_string()),
+                OffsetMarker::new(legacy_subfam),
+            ),
+            NameRecord::new(
+                3,
+                1,
+                0x0409,
+                NameId::UNIQUE_ID,
+                OffsetMarker::new(format!("{hash};{ps}")),
             ),
             NameRecord::new(
                 3,
@@ -222,66 +621,233 @@ pub(crate) fn generate_font_bytes(
                 1,
                 0x0409,
                 NameId::TYPOGRAPHIC_SUBFAMILY_NAME,
-                OffsetMarker::new(subfam.to_string()),
+                OffsetMarker::new(subfam),
             ),
         ];
+        // Axis and instance names of a variable font
+        for (name_id, value) in variation.iter().flat_map(|tables| &tables.names) {
+            recs.push(NameRecord::new(
+                3,
+                1,
+                0x0409,
+                *name_id,
+                OffsetMarker::new(value.clone()),
+            ));
+        }
         Name::new(recs)
     };
 
-    let last_char_index = if total_glyphs > 1 {
-        0xE000 + (total_glyphs - 2)
-    } else {
-        0xE000
-    };
+    // Supplementary plane codepoints are recorded as 0xFFFF in the 16-bit indices
+    let char_index = |ch: Option<&char>| ch.map_or(0xE000, |&ch| (ch as u32).min(0xFFFF) as u16);
+    let first_char_index = char_index(codepoints.iter().map(|(ch, _)| ch).min());
+    let last_char_index = char_index(codepoints.iter().map(|(ch, _)| ch).max());
+    let max_context = ligatures
+        .iter()
+        .map(|(sequence, _)| sequence.len() as u16)
+        .max()
+        .unwrap_or(0);
 
     let os2 = Os2 {
-        x_avg_char_width: advance_width as i16,
-        us_weight_class: 400,
+        x_avg_char_width: avg_char_width,
+        us_weight_class: weight_class,
         us_width_class: 5,
         panose_10: [0; 10],
-        fs_selection: SelectionFlags::REGULAR,
-        us_first_char_index: 0xE000,
+        fs_selection: match bold {
+            true => SelectionFlags::BOLD,
+            false => SelectionFlags::REGULAR,
+        },
+        ul_unicode_range_2: unicode_range_2(&codepoints),
+        ul_unicode_range_3: unicode_range_3(&codepoints),
+        us_first_char_index: first_char_index,
         us_last_char_index: last_char_index,
         s_typo_ascender: ascent,
         s_typo_descender: descent,
         s_typo_line_gap: 0,
-        us_win_ascent: ascent as u16,
-        us_win_descent: (-descent) as u16,
+        // Windows clips everything outside the win metrics
+        us_win_ascent: ascent.max(font_bbox.y_max).max(0) as u16,
+        us_win_descent: (-descent).max(-font_bbox.y_min).max(0) as u16,
         ul_code_page_range_1: Some(0),
         ul_code_page_range_2: Some(0),
         sx_height: Some(0),
         s_cap_height: Some(0),
         us_default_char: Some(0),
         us_break_char: Some(0),
-        us_max_context: Some(0),
+        us_max_context: Some(max_context),
         ..Default::default()
     };
 
-    let cmap = Cmap::from_mappings(codepoints).expect("failed to build cmap from glyph mappings");
+    let mut cmap = Cmap::from_mappings(codepoints)
+        .map_err(|err| font_error(format!("failed to build cmap: {err:?}")))?;
+    add_empty_bmp_subtables(&mut cmap);
 
     let mut fb = FontBuilder::new();
-    fb.add_table(&head).expect("add head");
-    fb.add_table(&hhea).expect("add hhea");
-    fb.add_table(&maxp).expect("add maxp");
-    fb.add_table(&hmtx).expect("add hmtx");
-    fb.add_table(&os2).expect("add os/2");
-    fb.add_table(&post).expect("add post");
-    fb.add_table(&name).expect("add name");
-    fb.add_table(&cmap).expect("add cmap");
-    fb.add_table(&glyf).expect("add glyf");
-    fb.add_table(&loca).expect("add loca");
-
-    fb.build()
+    fb.add_table(&head)
+        .and_then(|fb| fb.add_table(&hhea))
+        .and_then(|fb| fb.add_table(&maxp))
+        .and_then(|fb| fb.add_table(&hmtx))
+        .and_then(|fb| fb.add_table(&os2))
+        .and_then(|fb| fb.add_table(&post))
+        .and_then(|fb| fb.add_table(&name))
+        .and_then(|fb| fb.add_table(&cmap))
+        .map_err(|err| font_error(err.to_string()))?;
+    for (tag, table) in outline_tables {
+        fb.add_raw(tag, table);
+    }
+    if !ligatures.is_empty() {
+        fb.add_table(&ligature_gsub(&ligatures))
+            .map_err(|err| font_error(err.to_string()))?;
+    }
+    if !svg_documents.is_empty() {
+        let table = svg_table(&svg_documents).map_err(font_error)?;
+        fb.add_raw(Tag::new(b"SVG "), table);
+    }
+    if !color_glyphs.is_empty() {
+        let (colr, cpal) = color_glyphs.tables(first_layer).map_err(font_error)?;
+        fb.add_table(&colr)
+            .and_then(|fb| fb.add_table(&cpal))
+            .map_err(|err| font_error(err.to_string()))?;
+    }
+    if let Some(tables) = &variation {
+        fb.add_table(&tables.fvar)
+            .and_then(|fb| fb.add_table(&tables.gvar))
+            .and_then(|fb| fb.add_table(&tables.stat))
+            .map_err(|err| font_error(err.to_string()))?;
+    }
+
+    let mut bytes = fb.build();
+    if cff {
+        set_cff_sfnt_version(&mut bytes);
+    }
+    Ok(bytes)
+}
+
+/// Read the definition hash stored in the unique id name record of a generated font,
+/// which follows it with the PostScript name to tell the weights apart.
+pub(crate) fn embedded_hash(font_path: &path::Path) -> Option<String> {
+    let data = fs::read(font_path).ok()?;
+    let font = FontRef::new(&data).ok()?;
+    let name = font.name().ok()?;
+    let record = name
+        .name_record()
+        .iter()
+        .find(|record| record.name_id() == NameId::UNIQUE_ID)?;
+    let value = record.string(name.string_data()).ok()?.to_string();
+    value.split(';').next().map(str::to_string)
+}
+
+/// Bytes of every requested format of one weight, the web formats wrapping the main font.
+fn font_files(
+    module_name: &str,
+    hash: &str,
+    font: &FontConfig,
+    glyphs: &BTreeMap<Collection, Vec<PackIcon>>,
+    weight: Option<(&str, &FontWeight)>,
+) -> Result<Vec<(FontFormat, Vec<u8>)>, Error> {
+    let main_format = font.main_format();
+    let main = generate_font_bytes(module_name, hash, font, glyphs, main_format, weight)?;
+    let otf =
+        match main_format {
+            FontFormat::Ttf if font.formats.contains(&FontFormat::Otf) => Some(
+                generate_font_bytes(module_name, hash, font, glyphs, FontFormat::Otf, weight)?,
+            ),
+            _ => None,
+        };
+
+    let mut files = Vec::with_capacity(font.formats.len());
+    for &format in &font.formats {
+        let bytes = match format {
+            FontFormat::Otf => Ok(otf.clone().unwrap_or_else(|| main.clone())),
+            FontFormat::Ttf => Ok(main.clone()),
+            FontFormat::Woff => woff_bytes(&main),
+            FontFormat::Woff2 => woff2_bytes(&main),
+        }
+        .map_err(|message| Error::Font {
+            glyph: None,
+            message,
+        })?;
+        files.push((format, bytes));
+    }
+    Ok(files)
 }
 
-/// Build TTF "{module}.ttf"
+/// Build "{module}.ttf" and/or "{module}.otf" plus the requested web formats, which wrap
+/// the main font, and the same files for every static weight as "{module}-{weight}.ttf"
+/// and so on, returning the written paths.
 pub fn generate_font(
     path_hint: impl AsRef<path::Path>,
     module_path: impl AsRef<path::Path>,
+    hash: &str,
+    font: &FontConfig,
     glyphs: &mut BTreeMap<Collection, Vec<PackIcon>>,
-) {
-    let (font_path, module_basename) = font_path(path_hint, module_path);
-    let bytes = generate_font_bytes(&module_basename, glyphs);
-    let mut f = File::create(font_path).expect("cannot create output TTF");
-    f.write_all(&bytes).expect("failed to write TTF");
+) -> Result<Vec<path::PathBuf>, Error> {
+    let (_, module_basename) = font_path(&path_hint, &module_path, font.main_format());
+    let mut files = Vec::new();
+    for (format, bytes) in font_files(&module_basename, hash, font, glyphs, None)? {
+        files.push((font_path(&path_hint, &module_path, format).0, bytes));
+    }
+    for (name, weight) in font.static_weights() {
+        for (format, bytes) in
+            font_files(&module_basename, hash, font, glyphs, Some((name, weight)))?
+        {
+            files.push((
+                static_font_path(&path_hint, &module_path, name, format),
+                bytes,
+            ));
+        }
+    }
+
+    // The Iced module shows every icon through its character
+    for pack in glyphs.values_mut().flatten() {
+        let ch = char::from_u32(pack.codepoint).expect("valid PUA codepoint");
+        pack.icon = ch.to_string();
+    }
+
+    let mut written = Vec::with_capacity(files.len());
+    for (font_path, bytes) in files {
+        fs::write(&font_path, bytes).map_err(|source| Error::Io {
+            path: font_path.clone(),
+            source,
+        })?;
+        written.push(font_path);
+    }
+
+    Ok(written)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::GlyphOptions;
+
+    #[test]
+    fn running_out_of_glyph_ids_is_an_error() {
+        // One icon whose ligature needs a component glyph for more letters than fit
+        let name: String = (0x10000..0x10000 + u16::MAX as u32)
+            .filter_map(char::from_u32)
+            .collect();
+        let pack = PackIcon {
+            name,
+            enum_variant: "Wide".to_string(),
+            icon: r#"<svg viewBox="0 0 24 24"><path d="M2 2h20v20H2z"/></svg>"#.to_string(),
+            codepoint: 0xE000,
+            pinned: None,
+            options: GlyphOptions::default(),
+            order: 0,
+        };
+        let collection = Collection {
+            name: "local".to_string(),
+            local: true,
+        };
+        let glyphs = BTreeMap::from([(collection, vec![pack])]);
+        let font = FontConfig {
+            ligatures: true,
+            ..FontConfig::default()
+        };
+
+        let result = generate_font_bytes("icons", "", &font, &glyphs, FontFormat::Ttf, None);
+        assert!(
+            matches!(&result, Err(Error::Font { message, .. }) if message.contains("at most")),
+            "expected a glyph limit error, got {result:?}"
+        );
+    }
 }
@@ -0,0 +1,342 @@
+use std::collections::BTreeMap;
+
+use indexmap::IndexMap;
+use kurbo::{BezPath, PathEl, Shape};
+use write_fonts::{
+    tables::{
+        fvar::{AxisInstanceArrays, Fvar, InstanceRecord, VariationAxisRecord},
+        glyf::SimpleGlyph,
+        gvar::{GlyphDelta, GlyphDeltas, GlyphVariations, Gvar, Tent},
+        stat::{AxisRecord, AxisValue, AxisValueTableFlags, Stat},
+    },
+    types::{F2Dot14, Fixed, GlyphId, GlyphId16, NameId, Tag},
+};
+
+use crate::{
+    model::{FontWeight, REGULAR_WEIGHT},
+    utils::upper_first_char,
+};
+
+const WGHT: Tag = Tag::new(b"wght");
+
+/// Name ids from here on are free for the font to use.
+const FIRST_FONT_NAME_ID: u16 = 256;
+
+/// Advance and vertical metrics points every glyph has after its outline points.
+const PHANTOM_POINTS: usize = 4;
+
+/// Signed areas of the contours of an outline, positive for counter-clockwise ones.
+fn contour_areas(path: &BezPath) -> Vec<f64> {
+    let mut contours: Vec<BezPath> = Vec::new();
+    for el in path.elements() {
+        if matches!(el, PathEl::MoveTo(_)) || contours.is_empty() {
+            contours.push(BezPath::new());
+        }
+        contours.last_mut().expect("contour started").push(*el);
+    }
+    contours.iter().map(Shape::area).collect()
+}
+
+/// A weight with outlines of its own, the regular weight being the default.
+struct Master {
+    name: String,
+    weight: u16,
+    stroke: f64,
+    /// Region of the normalized axis the master influences: start, peak and end.
+    region: (f64, f64, f64),
+}
+
+/// Tables of a variable font and the strings their name ids refer to.
+pub(crate) struct VariationTables {
+    pub fvar: Fvar,
+    pub gvar: Gvar,
+    pub stat: Stat,
+    pub names: Vec<(NameId, String)>,
+}
+
+/// The `wght` axis of a variable font, collecting how each glyph moves between the
+/// regular outline and the masters.
+pub(crate) struct WeightAxis {
+    masters: Vec<Master>,
+    min: u16,
+    max: u16,
+    glyphs: BTreeMap<GlyphId16, Vec<GlyphDeltas>>,
+}
+
+impl WeightAxis {
+    pub(crate) fn new(weights: &IndexMap<String, FontWeight>) -> Self {
+        let mut sorted: Vec<(&String, &FontWeight)> = weights.iter().collect();
+        sorted.sort_by_key(|(_, weight)| weight.weight);
+        let min = sorted.first().map_or(REGULAR_WEIGHT, |(_, w)| w.weight);
+        let max = sorted.last().map_or(REGULAR_WEIGHT, |(_, w)| w.weight);
+        let min = min.min(REGULAR_WEIGHT);
+        let max = max.max(REGULAR_WEIGHT);
+
+        let normalize = |weight: u16| match weight.cmp(&REGULAR_WEIGHT) {
+            std::cmp::Ordering::Less => {
+                -((REGULAR_WEIGHT - weight) as f64 / (REGULAR_WEIGHT - min) as f64)
+            }
+            std::cmp::Ordering::Equal => 0.0,
+            std::cmp::Ordering::Greater => {
+                (weight - REGULAR_WEIGHT) as f64 / (max - REGULAR_WEIGHT) as f64
+            }
+        };
+
+        // Each master fades out towards its neighbours, the regular weight included,
+        // so the outline between two masters blends only those two
+        let mut locations: Vec<f64> = sorted.iter().map(|(_, w)| normalize(w.weight)).collect();
+        locations.push(0.0);
+        locations.sort_by(f64::total_cmp);
+        let masters = sorted
+            .into_iter()
+            .map(|(name, weight)| {
+                let peak = normalize(weight.weight);
+                let index = locations
+                    .iter()
+                    .position(|location| *location == peak)
+                    .expect("master location");
+                let start = locations[index.saturating_sub(1)];
+                let end = *locations.get(index + 1).unwrap_or(&peak);
+                Master {
+                    name: name.clone(),
+                    weight: weight.weight,
+                    stroke: weight.stroke,
+                    region: (start, peak, end),
+                }
+            })
+            .collect();
+
+        Self {
+            masters,
+            min,
+            max,
+            glyphs: BTreeMap::new(),
+        }
+    }
+
+    /// Stroke width factors of the masters, in the order [`WeightAxis::add`] expects
+    /// their outlines.
+    pub(crate) fn stroke_scales(&self) -> impl Iterator<Item = f64> + '_ {
+        self.masters.iter().map(|master| master.stroke)
+    }
+
+    /// Record the deltas of a glyph from its regular outline to the outline of every
+    /// master, all of them made of lines and quadratic curves in font units.
+    ///
+    /// Returns the regular glyph to write, built together with the masters so the points
+    /// line up with the deltas.
+    pub(crate) fn add(
+        &mut self,
+        glyph: GlyphId16,
+        regular: &BezPath,
+        masters: &[BezPath],
+    ) -> Result<SimpleGlyph, String> {
+        let paths: Vec<BezPath> = std::iter::once(regular).chain(masters).cloned().collect();
+        let glyphs = SimpleGlyph::interpolatable_glyphs_from_bezpaths(&paths).map_err(|_| {
+            "the outlines of its weights do not match point for point; \
+             strokes cut by clip paths or masks cannot vary in weight"
+                .to_string()
+        })?;
+        let (regular, masters) = glyphs.split_first().expect("regular glyph");
+
+        // Matching points are not enough, a contour running the other way at one weight
+        // would turn inside out on the way there
+        let directions = contour_areas(&paths[0]);
+        for path in &paths[1..] {
+            let flipped = directions
+                .iter()
+                .zip(contour_areas(path))
+                .any(|(regular, area)| regular * area < 0.0);
+            if flipped {
+                return Err(
+                    "the contours of its weights do not run in the same directions".to_string(),
+                );
+            }
+        }
+
+        let mut variations = Vec::new();
+        for (master, outline) in self.masters.iter().zip(masters) {
+            let points = regular.contours.iter().flat_map(|contour| contour.iter());
+            let moved = outline.contours.iter().flat_map(|contour| contour.iter());
+            let deltas: Vec<GlyphDelta> = points
+                .zip(moved)
+                .map(|(from, to)| GlyphDelta::required(to.x - from.x, to.y - from.y))
+                .chain((0..PHANTOM_POINTS).map(|_| GlyphDelta::required(0, 0)))
+                .collect();
+            if deltas.iter().all(|delta| delta.x == 0 && delta.y == 0) {
+                continue;
+            }
+
+            let (start, peak, end) = master.region;
+            let tent = Tent::new(
+                F2Dot14::from_f32(peak as f32),
+                Some((
+                    F2Dot14::from_f32(start as f32),
+                    F2Dot14::from_f32(end as f32),
+                )),
+            );
+            variations.push(GlyphDeltas::new(vec![tent], deltas));
+        }
+        if !variations.is_empty() {
+            self.glyphs.insert(glyph, variations);
+        }
+        Ok(regular.clone())
+    }
+
+    /// Build the `fvar`, `gvar` and `STAT` tables for a font of `num_glyphs` glyphs.
+    pub(crate) fn tables(self, num_glyphs: u16) -> Result<VariationTables, String> {
+        let axis_name = NameId::new(FIRST_FONT_NAME_ID);
+        let mut names = vec![(axis_name, "Weight".to_string())];
+
+        // Named instances and axis values in weight order, the regular one among them
+        let mut instances = vec![(REGULAR_WEIGHT, NameId::SUBFAMILY_NAME)];
+        for (index, master) in self.masters.iter().enumerate() {
+            let name_id = NameId::new(FIRST_FONT_NAME_ID + 1 + index as u16);
+            names.push((name_id, upper_first_char(&master.name)));
+            instances.push((master.weight, name_id));
+        }
+        instances.sort_by_key(|(weight, _)| *weight);
+
+        let axis = VariationAxisRecord {
+            axis_tag: WGHT,
+            min_value: Fixed::from_f64(self.min as f64),
+            default_value: Fixed::from_f64(REGULAR_WEIGHT as f64),
+            max_value: Fixed::from_f64(self.max as f64),
+            flags: 0,
+            axis_name_id: axis_name,
+        };
+        let records = instances
+            .iter()
+            .map(|&(weight, name_id)| InstanceRecord {
+                subfamily_name_id: name_id,
+                flags: 0,
+                coordinates: vec![Fixed::from_f64(weight as f64)],
+                post_script_name_id: None,
+            })
+            .collect();
+        let fvar = Fvar::new(AxisInstanceArrays::new(vec![axis], records));
+
+        let mut glyphs = self.glyphs;
+        let variations = (0..num_glyphs)
+            .map(|id| {
+                let deltas = glyphs.remove(&GlyphId16::new(id)).unwrap_or_default();
+                GlyphVariations::new(GlyphId::from(id), deltas)
+            })
+            .collect();
+        let gvar = Gvar::new(variations, 1).map_err(|err| err.to_string())?;
+
+        let values = instances
+            .iter()
+            .map(|&(weight, name_id)| {
+                let flags = match weight == REGULAR_WEIGHT {
+                    true => AxisValueTableFlags::ELIDABLE_AXIS_VALUE_NAME,
+                    false => AxisValueTableFlags::empty(),
+                };
+                AxisValue::format_1(0, flags, name_id, Fixed::from_f64(weight as f64))
+            })
+            .collect();
+        let stat = Stat::new(
+            vec![AxisRecord::new(WGHT, axis_name, 0)],
+            values,
+            NameId::SUBFAMILY_NAME,
+        );
+
+        Ok(VariationTables {
+            fvar,
+            gvar,
+            stat,
+            names,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use write_fonts::{
+        dump_table,
+        read::{FontData, FontRead, tables::gvar::Gvar as ReadGvar},
+    };
+
+    use super::*;
+    use crate::generator::font::svg::{Stroking, SvgOptions, svg_to_quadratics};
+
+    const LINE: &str = r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 1000 1000"><path d="M100 500H900" fill="none" stroke="black" stroke-width="100"/></svg>"#;
+
+    fn line_at(scale: f64) -> BezPath {
+        let options = SvgOptions {
+            stroking: Stroking::Interpolatable(scale),
+            remove_overlaps: false,
+            color: false,
+        };
+        svg_to_quadratics(LINE, options).unwrap().outline
+    }
+
+    #[test]
+    fn stroked_line_moves_by_half_the_stroke_change() {
+        let weights = IndexMap::from([
+            (
+                "bold".to_string(),
+                FontWeight {
+                    weight: 700,
+                    stroke: 2.0,
+                },
+            ),
+            (
+                "light".to_string(),
+                FontWeight {
+                    weight: 300,
+                    stroke: 0.5,
+                },
+            ),
+        ]);
+        let mut axis = WeightAxis::new(&weights);
+        let scales: Vec<f64> = axis.stroke_scales().collect();
+        assert_eq!(scales, [0.5, 2.0]);
+
+        let masters: Vec<BezPath> = scales.iter().map(|scale| line_at(*scale)).collect();
+        let regular = axis
+            .add(GlyphId16::new(1), &line_at(1.0), &masters)
+            .unwrap();
+        let points: Vec<_> = regular
+            .contours
+            .iter()
+            .flat_map(|contour| contour.iter())
+            .collect();
+        assert!(!points.is_empty());
+
+        let tables = axis.tables(2).unwrap();
+        let bytes = dump_table(&tables.gvar).unwrap();
+        let gvar = ReadGvar::read(FontData::new(&bytes)).unwrap();
+        assert!(
+            gvar.glyph_variation_data(GlyphId::new(0))
+                .unwrap()
+                .is_none()
+        );
+
+        let data = gvar.glyph_variation_data(GlyphId::new(1)).unwrap().unwrap();
+        let mut peaks = Vec::new();
+        for tuple in data.tuples() {
+            let peak = tuple.peak().get(0).unwrap().to_f32();
+            // Half of the stroke width change on each side of the line, 100 units wide
+            let offset = match peak {
+                -1.0 => -25,
+                1.0 => 50,
+                _ => panic!("unexpected peak {peak}"),
+            };
+            peaks.push(peak);
+
+            let deltas: Vec<_> = tuple.deltas().collect();
+            assert_eq!(deltas.len(), points.len() + PHANTOM_POINTS);
+            for delta in deltas {
+                let Some(point) = points.get(delta.position as usize) else {
+                    assert_eq!((delta.x_delta, delta.y_delta), (0, 0));
+                    continue;
+                };
+                let side = (point.y as i32 - 500).signum();
+                assert_ne!(side, 0, "point on the centre line");
+                assert_eq!((delta.x_delta, delta.y_delta), (0, side * offset));
+            }
+        }
+        assert_eq!(peaks, [-1.0, 1.0]);
+    }
+}
@@ -0,0 +1,363 @@
+use std::io::Write;
+
+use brotli::enc::{BrotliEncoderParams, backward_references::BrotliEncoderMode};
+use flate2::{Compression, write::ZlibEncoder};
+use write_fonts::read::FontRef;
+
+const WOFF_HEADER_LEN: usize = 44;
+const WOFF_ENTRY_LEN: usize = 20;
+const WOFF2_HEADER_LEN: usize = 48;
+
+/// Tags with a one byte index in the WOFF2 table directory, in spec order.
+const WOFF2_KNOWN_TAGS: [&[u8; 4]; 63] = [
+    b"cmap", b"head", b"hhea", b"hmtx", b"maxp", b"name", b"OS/2", b"post", b"cvt ", b"fpgm",
+    b"glyf", b"loca", b"prep", b"CFF ", b"VORG", b"EBDT", b"EBLC", b"gasp", b"hdmx", b"kern",
+    b"LTSH", b"PCLT", b"VDMX", b"vhea", b"vmtx", b"BASE", b"GDEF", b"GPOS", b"GSUB", b"EBSC",
+    b"JSTF", b"MATH", b"CBDT", b"CBLC", b"COLR", b"CPAL", b"SVG ", b"sbix", b"acnt", b"avar",
+    b"bdat", b"bloc", b"bsln", b"cvar", b"fdsc", b"feat", b"fmtx", b"fvar", b"gvar", b"hsty",
+    b"just", b"lcar", b"mort", b"morx", b"opbd", b"prop", b"trak", b"Zapf", b"Silf", b"Glat",
+    b"Gloc", b"Feat", b"Sill",
+];
+
+struct SfntTable<'a> {
+    tag: [u8; 4],
+    checksum: u32,
+    data: &'a [u8],
+}
+
+struct Sfnt<'a> {
+    flavor: u32,
+    tables: Vec<SfntTable<'a>>,
+}
+
+impl Sfnt<'_> {
+    /// Size of the font once decoded back into a plain sfnt.
+    fn total_size(&self) -> usize {
+        let directory = 12 + 16 * self.tables.len();
+        directory
+            + self
+                .tables
+                .iter()
+                .map(|t| padded(t.data.len()))
+                .sum::<usize>()
+    }
+}
+
+fn read_sfnt(sfnt: &[u8]) -> Result<Sfnt<'_>, String> {
+    let font = FontRef::new(sfnt).map_err(|err| format!("invalid sfnt: {err}"))?;
+    let mut tables = font
+        .table_directory
+        .table_records()
+        .iter()
+        .map(|record| {
+            let tag = record.tag();
+            let data = font
+                .table_data(tag)
+                .ok_or_else(|| format!("missing data for table '{tag}'"))?;
+            Ok(SfntTable {
+                tag: tag.to_be_bytes(),
+                checksum: record.checksum(),
+                data: data.as_bytes(),
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    tables.sort_by_key(|table| table.tag);
+
+    Ok(Sfnt {
+        flavor: font.table_directory.sfnt_version(),
+        tables,
+    })
+}
+
+fn padded(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn pad(out: &mut Vec<u8>) {
+    out.resize(padded(out.len()), 0);
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn put_u32_at(out: &mut [u8], at: usize, value: u32) {
+    out[at..at + 4].copy_from_slice(&value.to_be_bytes());
+}
+
+fn to_u32(len: usize) -> Result<u32, String> {
+    u32::try_from(len).map_err(|_| "font exceeds 4 GiB".to_string())
+}
+
+/// Write `value` as a WOFF2 UIntBase128.
+fn put_base128(out: &mut Vec<u8>, value: u32) {
+    let mut bytes = [0u8; 5];
+    let mut len = 0;
+    let mut rest = value;
+    loop {
+        bytes[len] = (rest & 0x7F) as u8;
+        len += 1;
+        rest >>= 7;
+        if rest == 0 {
+            break;
+        }
+    }
+    for (index, byte) in bytes[..len].iter().rev().enumerate() {
+        let more = if index + 1 < len { 0x80 } else { 0 };
+        out.push(byte | more);
+    }
+}
+
+/// Wrap the tables of a TTF into a WOFF 1.0 file, zlib compressing each table.
+pub(crate) fn woff_bytes(sfnt: &[u8]) -> Result<Vec<u8>, String> {
+    let font = read_sfnt(sfnt)?;
+    let num_tables = font.tables.len();
+
+    let mut out = Vec::with_capacity(sfnt.len());
+    out.extend_from_slice(b"wOFF");
+    put_u32(&mut out, font.flavor);
+    put_u32(&mut out, 0); // length, patched below
+    put_u16(&mut out, num_tables as u16);
+    put_u16(&mut out, 0);
+    put_u32(&mut out, to_u32(font.total_size())?);
+    put_u16(&mut out, 1);
+    put_u16(&mut out, 0);
+    // No metadata or private block
+    out.extend_from_slice(&[0; 20]);
+    debug_assert_eq!(out.len(), WOFF_HEADER_LEN);
+
+    let directory_start = out.len();
+    out.resize(directory_start + WOFF_ENTRY_LEN * num_tables, 0);
+
+    for (index, table) in font.tables.iter().enumerate() {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+        encoder
+            .write_all(table.data)
+            .map_err(|err| format!("failed to compress table: {err}"))?;
+        let compressed = encoder
+            .finish()
+            .map_err(|err| format!("failed to compress table: {err}"))?;
+        // Tables that do not shrink must be stored as is
+        let stored = match compressed.len() < table.data.len() {
+            true => compressed.as_slice(),
+            false => table.data,
+        };
+
+        let offset = out.len();
+        out.extend_from_slice(stored);
+        pad(&mut out);
+
+        let entry = directory_start + index * WOFF_ENTRY_LEN;
+        out[entry..entry + 4].copy_from_slice(&table.tag);
+        put_u32_at(&mut out, entry + 4, to_u32(offset)?);
+        put_u32_at(&mut out, entry + 8, to_u32(stored.len())?);
+        put_u32_at(&mut out, entry + 12, to_u32(table.data.len())?);
+        put_u32_at(&mut out, entry + 16, table.checksum);
+    }
+
+    let length = to_u32(out.len())?;
+    put_u32_at(&mut out, 8, length);
+    Ok(out)
+}
+
+/// Wrap the tables of a TTF into a WOFF 2.0 file as a single brotli stream.
+///
+/// `glyf` and `loca` are stored with the null transform, so the decoded font is byte for
+/// byte the table data of the TTF.
+pub(crate) fn woff2_bytes(sfnt: &[u8]) -> Result<Vec<u8>, String> {
+    let font = read_sfnt(sfnt)?;
+
+    let mut directory = Vec::new();
+    let mut stream = Vec::with_capacity(sfnt.len());
+    for table in &font.tables {
+        let known = WOFF2_KNOWN_TAGS.iter().position(|tag| **tag == table.tag);
+        // glyf and loca default to the glyph transform, version 3 opts out of it
+        let transform: u8 = match &table.tag {
+            b"glyf" | b"loca" => 3 << 6,
+            _ => 0,
+        };
+        match known {
+            Some(index) => directory.push(transform | index as u8),
+            None => {
+                directory.push(transform | 63);
+                directory.extend_from_slice(&table.tag);
+            }
+        }
+        put_base128(&mut directory, to_u32(table.data.len())?);
+        stream.extend_from_slice(table.data);
+    }
+
+    let params = BrotliEncoderParams {
+        mode: BrotliEncoderMode::BROTLI_MODE_FONT,
+        quality: 11,
+        size_hint: stream.len(),
+        ..Default::default()
+    };
+    let mut compressed = Vec::new();
+    brotli::BrotliCompress(&mut stream.as_slice(), &mut compressed, &params)
+        .map_err(|err| format!("failed to compress tables: {err}"))?;
+
+    let mut out = Vec::with_capacity(WOFF2_HEADER_LEN + directory.len() + compressed.len());
+    out.extend_from_slice(b"wOF2");
+    put_u32(&mut out, font.flavor);
+    put_u32(&mut out, 0); // length, patched below
+    put_u16(&mut out, font.tables.len() as u16);
+    put_u16(&mut out, 0);
+    put_u32(&mut out, to_u32(font.total_size())?);
+    put_u32(&mut out, to_u32(compressed.len())?);
+    put_u16(&mut out, 1);
+    put_u16(&mut out, 0);
+    // No metadata or private block
+    out.extend_from_slice(&[0; 20]);
+    debug_assert_eq!(out.len(), WOFF2_HEADER_LEN);
+
+    out.extend_from_slice(&directory);
+    out.extend_from_slice(&compressed);
+    pad(&mut out);
+
+    let length = to_u32(out.len())?;
+    put_u32_at(&mut out, 8, length);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::ZlibDecoder;
+    use write_fonts::{FontBuilder, types::Tag};
+
+    use super::*;
+
+    fn u16_at(data: &[u8], at: usize) -> u16 {
+        u16::from_be_bytes(data[at..at + 2].try_into().unwrap())
+    }
+
+    fn u32_at(data: &[u8], at: usize) -> u32 {
+        u32::from_be_bytes(data[at..at + 4].try_into().unwrap())
+    }
+
+    /// A font of raw tables, some compressible, `glyf` and `loca` and an unknown tag.
+    fn sample_font() -> Vec<u8> {
+        let repeated: Vec<u8> = (0..400u32).map(|i| (i % 7) as u8).collect();
+        FontBuilder::new()
+            .add_raw(Tag::new(b"glyf"), repeated.clone())
+            .add_raw(Tag::new(b"loca"), vec![0, 0, 0, 4, 0, 8])
+            .add_raw(Tag::new(b"name"), b"icon".to_vec())
+            .add_raw(Tag::new(b"Zzzz"), repeated)
+            .build()
+    }
+
+    /// Build a plain sfnt from decoded tables and check read-fonts sees the original ones.
+    fn assert_round_trip(sfnt: &[u8], decoded: Vec<([u8; 4], Vec<u8>)>) {
+        let original = FontRef::new(sfnt).unwrap();
+        assert_eq!(
+            decoded.len(),
+            original.table_directory.table_records().len()
+        );
+
+        let mut builder = FontBuilder::new();
+        for (tag, data) in &decoded {
+            builder.add_raw(Tag::new(tag), data.clone());
+        }
+        let rebuilt = builder.build();
+        let rebuilt = FontRef::new(&rebuilt).unwrap();
+        for record in original.table_directory.table_records() {
+            let tag = record.tag();
+            assert_eq!(
+                rebuilt.table_data(tag).unwrap().as_bytes(),
+                original.table_data(tag).unwrap().as_bytes(),
+                "table '{tag}'"
+            );
+        }
+    }
+
+    #[test]
+    fn woff_round_trips() {
+        let sfnt = sample_font();
+        let woff = woff_bytes(&sfnt).unwrap();
+        assert_eq!(&woff[..4], b"wOFF");
+        assert_eq!(u32_at(&woff, 8) as usize, woff.len());
+        assert_eq!(
+            u32_at(&woff, 16) as usize,
+            read_sfnt(&sfnt).unwrap().total_size()
+        );
+
+        let decoded = (0..u16_at(&woff, 12) as usize)
+            .map(|index| {
+                let entry = WOFF_HEADER_LEN + index * WOFF_ENTRY_LEN;
+                let offset = u32_at(&woff, entry + 4) as usize;
+                let stored = &woff[offset..offset + u32_at(&woff, entry + 8) as usize];
+                let length = u32_at(&woff, entry + 12) as usize;
+                let data = match stored.len() < length {
+                    true => {
+                        let mut data = Vec::new();
+                        ZlibDecoder::new(stored).read_to_end(&mut data).unwrap();
+                        data
+                    }
+                    false => stored.to_vec(),
+                };
+                assert_eq!(data.len(), length);
+                (woff[entry..entry + 4].try_into().unwrap(), data)
+            })
+            .collect();
+        assert_round_trip(&sfnt, decoded);
+    }
+
+    #[test]
+    fn woff2_round_trips() {
+        let sfnt = sample_font();
+        let woff2 = woff2_bytes(&sfnt).unwrap();
+        assert_eq!(&woff2[..4], b"wOF2");
+        assert_eq!(u32_at(&woff2, 8) as usize, woff2.len());
+
+        let mut at = WOFF2_HEADER_LEN;
+        let mut directory = Vec::new();
+        for _ in 0..u16_at(&woff2, 12) {
+            let flags = woff2[at];
+            at += 1;
+            let tag: [u8; 4] = match flags & 63 {
+                63 => {
+                    at += 4;
+                    woff2[at - 4..at].try_into().unwrap()
+                }
+                index => *WOFF2_KNOWN_TAGS[index as usize],
+            };
+            // Only the null transform is written, so there is no transformed length
+            let expected = match &tag {
+                b"glyf" | b"loca" => 3,
+                _ => 0,
+            };
+            assert_eq!(flags >> 6, expected, "transform of '{tag:?}'");
+            let mut length = 0usize;
+            loop {
+                let byte = woff2[at];
+                at += 1;
+                length = length << 7 | (byte & 0x7F) as usize;
+                if byte & 0x80 == 0 {
+                    break;
+                }
+            }
+            directory.push((tag, length));
+        }
+
+        let compressed = &woff2[at..at + u32_at(&woff2, 20) as usize];
+        let mut stream = Vec::new();
+        brotli::BrotliDecompress(&mut &compressed[..], &mut stream).unwrap();
+
+        let mut offset = 0;
+        let decoded = directory
+            .into_iter()
+            .map(|(tag, length)| {
+                offset += length;
+                (tag, stream[offset - length..offset].to_vec())
+            })
+            .collect();
+        assert_eq!(offset, stream.len());
+        assert_round_trip(&sfnt, decoded);
+    }
+}
@@ -1,3 +1,5 @@
+mod codepoints;
 pub mod font;
 
-pub(crate) use font::{font_path, generate_font};
+pub(crate) use codepoints::CodepointMap;
+pub(crate) use font::{embedded_hash, font_path, generate_font, static_font_path};
@@ -0,0 +1,55 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use super::types::IconifyIcon;
+
+/// On-disk store of resolved Iconify icons, one JSON file per `prefix/icon`.
+pub(crate) struct IconCache {
+    dir: PathBuf,
+}
+
+impl IconCache {
+    pub(crate) fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+        }
+    }
+
+    /// `None` unless both names are Iconify names, so they cannot leave the cache directory.
+    fn entry_path(&self, collection: &str, icon: &str) -> Option<PathBuf> {
+        let valid = |name: &str| {
+            !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        };
+        if !valid(collection) || !valid(icon) {
+            return None;
+        }
+        Some(self.dir.join(collection).join(icon).with_extension("json"))
+    }
+
+    /// Unreadable or corrupt entries are treated as misses.
+    pub(crate) fn get(&self, collection: &str, icon: &str) -> Option<IconifyIcon> {
+        let content = fs::read(self.entry_path(collection, icon)?).ok()?;
+        serde_json::from_slice(&content).ok()
+    }
+
+    /// Store an icon for later builds. Best effort: an icon that cannot be written is
+    /// fetched again next time instead of failing this build.
+    pub(crate) fn put(&self, collection: &str, icon: &str, data: &IconifyIcon) {
+        let Some(path) = self.entry_path(collection, icon) else {
+            return;
+        };
+        if let Some(parent) = path.parent()
+            && fs::create_dir_all(parent).is_err()
+        {
+            return;
+        }
+
+        let content = serde_json::to_vec_pretty(data).expect("Failed to serialize cached icon");
+        let _ = fs::write(&path, content);
+    }
+}
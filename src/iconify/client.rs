@@ -1,39 +1,55 @@
-use crate::model::Collection;
+use crate::{Error, model::Collection};
 
-use super::types::IconifyResponse;
+use super::types::{IconifyCollectionInfo, IconifyResponse};
 use ::reqwest::Url;
 use reqwest::blocking as reqwest;
 
-pub(crate) fn fetch_collection(collection: &Collection, icons: &[&str]) -> IconifyResponse {
-    let base = format!("https://api.iconify.design/{}.json", collection.name);
+/// Request the icons from each host in turn, returning the first success.
+pub(crate) fn fetch_collection(
+    collection: &Collection,
+    icons: &[&str],
+    hosts: &[String],
+) -> Result<IconifyResponse, Error> {
     let joined = icons.join(",");
+    let mut last_error = None;
 
-    let url = Url::parse_with_params(&base, &[("icons", joined)]).unwrap_or_else(|e| {
-        panic!(
-            "failed to build Iconify URL for collection '{}': {e}",
-            collection.name
-        )
-    });
+    for host in hosts {
+        let base = format!("{}/{}.json", host.trim_end_matches('/'), collection.name);
+        let url = Url::parse_with_params(&base, &[("icons", &joined)]).map_err(|err| {
+            Error::InvalidUrl {
+                url: host.clone(),
+                message: err.to_string(),
+            }
+        })?;
 
-    let resp = reqwest::get(url)
-        .unwrap_or_else(|e| {
-            panic!(
-                "failed to GET Iconify for collection '{}': {e}",
-                collection.name
-            )
-        })
-        .error_for_status()
-        .unwrap_or_else(|e| {
-            panic!(
-                "non-success HTTP status for collection '{}': {e}",
-                collection.name
-            )
-        });
+        let response = reqwest::get(url)
+            .and_then(|resp| resp.error_for_status())
+            .and_then(|resp| resp.json());
 
-    resp.json().unwrap_or_else(|e| {
-        panic!(
-            "failed to parse Iconify JSON for collection '{}': {e}",
-            collection.name
-        )
+        match response {
+            Ok(parsed) => return Ok(parsed),
+            Err(source) => {
+                last_error = Some(Error::Network {
+                    collection: collection.name.clone(),
+                    source,
+                })
+            }
+        }
+    }
+
+    Err(last_error.expect("at least one Iconify host"))
+}
+
+/// List every icon name of the collection, used for suggestions. Best effort: any
+/// failure just means no suggestions.
+pub(crate) fn fetch_icon_names(collection: &Collection, hosts: &[String]) -> Option<Vec<String>> {
+    hosts.iter().find_map(|host| {
+        let base = format!("{}/collection", host.trim_end_matches('/'));
+        let url = Url::parse_with_params(&base, &[("prefix", &collection.name)]).ok()?;
+        let info: IconifyCollectionInfo = reqwest::get(url)
+            .and_then(|resp| resp.error_for_status())
+            .and_then(|resp| resp.json())
+            .ok()?;
+        Some(info.into_names())
     })
 }
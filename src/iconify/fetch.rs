@@ -1,13 +1,71 @@
-use std::collections::{BTreeMap, HashSet};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::PathBuf,
+};
 
 use crate::{
-    generator::font::wrap_iconify_svg,
+    Error, MissingIcon,
     model::{Collection, PackIcon},
+    utils::closest_matches,
+};
+
+use super::{
+    cache::IconCache,
+    client::{fetch_collection, fetch_icon_names},
+    lock::Lockfile,
+    types::IconifyIcon,
+    vendored::load_collection,
 };
 
-use super::client::fetch_collection;
+const MAX_SUGGESTIONS: usize = 3;
+
+fn missing_icons<'a>(
+    unknown: &[&str],
+    names: impl IntoIterator<Item = &'a str> + Clone,
+) -> Vec<MissingIcon> {
+    unknown
+        .iter()
+        .map(|name| MissingIcon {
+            name: name.to_string(),
+            suggestions: closest_matches(name, names.clone(), MAX_SUGGESTIONS),
+        })
+        .collect()
+}
+
+/// Where icons may be resolved from besides the Iconify API.
+#[derive(Debug, Clone)]
+pub(crate) struct FetchOptions {
+    pub cache_dir: Option<PathBuf>,
+    /// Iconify API base urls, tried in order.
+    pub api_hosts: Vec<String>,
+    /// Directory of vendored `@iconify/json` collection files.
+    pub json_dir: Option<PathBuf>,
+    /// Never touch the network; every icon must already be cached.
+    pub offline: bool,
+    pub lock_path: PathBuf,
+    /// Ignore pinned icons and re-resolve everything from upstream.
+    pub refresh_lock: bool,
+}
+
+pub(crate) fn fetch_icons(
+    glyphs: &mut BTreeMap<Collection, Vec<PackIcon>>,
+    options: &FetchOptions,
+) -> Result<(), Error> {
+    let cache = options.cache_dir.as_ref().map(IconCache::new);
+    // Refreshing must see upstream changes, so stale cache entries are skipped too
+    let read_cache = cache
+        .as_ref()
+        .filter(|_| !options.refresh_lock || options.offline);
+
+    let locked = match options.refresh_lock {
+        false => Lockfile::load(&options.lock_path)?,
+        true => Lockfile::default(),
+    };
+    // An existing lock is the only source of icons until it is refreshed
+    let exclusive = !options.refresh_lock && options.lock_path.exists();
+    let mut lock = Lockfile::default();
+    let mut not_found = BTreeMap::new();
 
-pub(crate) fn fetch_icons(glyphs: &mut BTreeMap<Collection, Vec<PackIcon>>) {
     for (collection, entries) in glyphs.iter_mut() {
         if collection.local {
             continue;
@@ -18,14 +76,17 @@ pub(crate) fn fetch_icons(glyphs: &mut BTreeMap<Collection, Vec<PackIcon>>) {
             .map(|pack| {
                 let trimmed = pack.icon.trim();
                 if trimmed.is_empty() {
-                    panic!(
-                        "Icon '{}' for collection '{:?}' must not be empty",
-                        pack.enum_variant, collection
-                    );
+                    return Err(Error::InvalidGlyph {
+                        glyph: pack.enum_variant.clone(),
+                        message: format!(
+                            "icon name for collection '{}' must not be empty",
+                            collection.name
+                        ),
+                    });
                 }
-                trimmed.to_string()
+                Ok(trimmed.to_string())
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
 
         let mut seen = HashSet::new();
         let wanted: Vec<&str> = cleaned
@@ -34,31 +95,118 @@ pub(crate) fn fetch_icons(glyphs: &mut BTreeMap<Collection, Vec<PackIcon>>) {
             .filter(|name| seen.insert(*name))
             .collect();
 
-        let parsed = fetch_collection(collection, &wanted);
+        let mut resolved = BTreeMap::<&str, IconifyIcon>::new();
+        let mut missing = Vec::new();
+        for name in wanted {
+            match locked.get(&options.lock_path, &collection.name, name)? {
+                Some(icon) => {
+                    resolved.insert(name, icon);
+                }
+                None => missing.push(name),
+            }
+        }
 
-        if parsed.prefix != *collection.name {
-            panic!(
-                "Iconify prefix mismatch: requested collection '{}', got '{}'",
-                collection.name, parsed.prefix
-            );
+        if exclusive && !missing.is_empty() {
+            let keys: Vec<String> = missing
+                .iter()
+                .map(|name| format!("'{}::{name}'", collection.name))
+                .collect();
+            return Err(Error::Lock {
+                path: options.lock_path.clone(),
+                message: format!(
+                    "{} not pinned, refresh the lock with ICON_REFRESH_LOCK=1 or `Icon::refresh_lock` to add new icons",
+                    keys.join(", ")
+                ),
+            });
         }
 
-        let fetched = (parsed.icons, parsed.width, parsed.height);
+        let vendored = match &options.json_dir {
+            Some(dir) if !missing.is_empty() => load_collection(dir, collection)?,
+            _ => None,
+        };
+        if let Some(vendored) = vendored {
+            let mut unknown = Vec::new();
+            for name in missing.drain(..) {
+                match vendored.icon(name) {
+                    Some(icon) => {
+                        resolved.insert(name, icon);
+                    }
+                    None => unknown.push(name),
+                }
+            }
 
-        for (pack, clean_name) in entries.iter_mut().zip(cleaned.into_iter()) {
-            let (ref icons, width, height) = fetched;
-            let icon = icons.get(&clean_name).unwrap_or_else(|| {
-                panic!(
-                    "Iconify missing icon '{}' for collection '{}'",
-                    clean_name, collection.name
-                )
-            });
+            if !unknown.is_empty() {
+                let icons = missing_icons(&unknown, vendored.names());
+                not_found.insert(collection.name.clone(), icons);
+                continue;
+            }
+        }
 
-            pack.icon = wrap_iconify_svg(
-                &icon.body,
-                icon.width.unwrap_or(width),
-                icon.height.unwrap_or(height),
-            );
+        missing.retain(
+            |name| match read_cache.and_then(|c| c.get(&collection.name, name)) {
+                Some(icon) => {
+                    resolved.insert(name, icon);
+                    false
+                }
+                None => true,
+            },
+        );
+
+        if !missing.is_empty() {
+            if options.offline {
+                return Err(Error::NotCached {
+                    collection: collection.name.clone(),
+                    icons: missing.iter().map(|name| name.to_string()).collect(),
+                });
+            }
+
+            let parsed = fetch_collection(collection, &missing, &options.api_hosts)?;
+
+            if parsed.prefix != *collection.name {
+                return Err(Error::PrefixMismatch {
+                    collection: collection.name.clone(),
+                    received: parsed.prefix,
+                });
+            }
+
+            let mut unknown = Vec::new();
+            for name in missing {
+                let icon = parsed
+                    .icon(name)
+                    .filter(|_| !parsed.not_found.iter().any(|n| n == name));
+                let Some(icon) = icon else {
+                    unknown.push(name);
+                    continue;
+                };
+
+                if let Some(cache) = &cache {
+                    cache.put(&collection.name, name, &icon);
+                }
+                resolved.insert(name, icon);
+            }
+
+            if !unknown.is_empty() {
+                let names = fetch_icon_names(collection, &options.api_hosts).unwrap_or_default();
+                let icons = missing_icons(&unknown, names.iter().map(String::as_str));
+                not_found.insert(collection.name.clone(), icons);
+                continue;
+            }
+        }
+
+        for (name, icon) in &resolved {
+            lock.insert(&collection.name, name, icon);
+        }
+
+        for (pack, clean_name) in entries.iter_mut().zip(&cleaned) {
+            pack.icon = resolved[clean_name.as_str()].to_svg();
         }
     }
+
+    if !not_found.is_empty() {
+        return Err(Error::MissingIcons {
+            collections: not_found,
+        });
+    }
+
+    lock.save(&options.lock_path)
 }
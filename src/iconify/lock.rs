@@ -0,0 +1,215 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{Error, utils::hex_upper};
+
+use super::types::{IconifyIcon, IconifyTransform};
+
+const LOCK_HEADER: &str = "# Generated by icon; pins the Iconify icons used by the definition.\n\n";
+
+/// Contents of `icons.lock`, keyed by `collection::icon`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct Lockfile {
+    #[serde(default)]
+    icons: BTreeMap<String, LockedIcon>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct LockedIcon {
+    width: f64,
+    height: f64,
+    #[serde(flatten)]
+    transform: IconifyTransform,
+    hash: String,
+    body: String,
+}
+
+fn lock_key(collection: &str, icon: &str) -> String {
+    format!("{collection}::{icon}")
+}
+
+fn content_hash(icon: &IconifyIcon) -> String {
+    let serialized = serde_json::to_vec(icon).expect("Failed to serialize locked icon");
+    hex_upper(Sha256::digest(&serialized))
+}
+
+impl Lockfile {
+    /// Read the lockfile, an absent file is an empty lock.
+    pub(crate) fn load(path: &Path) -> Result<Self, Error> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+
+        let lock_error = |message| Error::Lock {
+            path: path.to_path_buf(),
+            message,
+        };
+        let content = fs::read_to_string(path)
+            .map_err(|err| lock_error(format!("failed to read file: {err}")))?;
+        toml::from_str(&content).map_err(|err| lock_error(format!("failed to parse TOML: {err}")))
+    }
+
+    /// Write the lockfile unless the file on disk already matches.
+    pub(crate) fn save(&self, path: &Path) -> Result<(), Error> {
+        if self.icons.is_empty() && !path.exists() {
+            return Ok(());
+        }
+
+        let serialized = toml::to_string(self).expect("Failed to serialize lockfile");
+        let content = format!("{LOCK_HEADER}{serialized}");
+        if fs::read_to_string(path).is_ok_and(|existing| existing == content) {
+            return Ok(());
+        }
+
+        fs::write(path, content).map_err(|source| Error::Io {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    /// Look up a pinned icon, verifying it was not edited by hand.
+    pub(crate) fn get(
+        &self,
+        path: &Path,
+        collection: &str,
+        icon: &str,
+    ) -> Result<Option<IconifyIcon>, Error> {
+        let key = lock_key(collection, icon);
+        let Some(locked) = self.icons.get(&key) else {
+            return Ok(None);
+        };
+
+        let data = IconifyIcon {
+            body: locked.body.clone(),
+            width: Some(locked.width),
+            height: Some(locked.height),
+            transform: locked.transform.clone(),
+        };
+        if content_hash(&data) != locked.hash {
+            return Err(Error::Lock {
+                path: path.to_path_buf(),
+                message: format!("content hash mismatch for '{key}'"),
+            });
+        }
+
+        Ok(Some(data))
+    }
+
+    pub(crate) fn insert(&mut self, collection: &str, icon: &str, data: &IconifyIcon) {
+        let locked = LockedIcon {
+            width: data.width.unwrap_or_default(),
+            height: data.height.unwrap_or_default(),
+            transform: data.transform.clone(),
+            hash: content_hash(data),
+            body: data.body.clone(),
+        };
+        self.icons.insert(lock_key(collection, icon), locked);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::BTreeMap, path::PathBuf};
+
+    use super::*;
+    use crate::{
+        iconify::{
+            cache::IconCache,
+            fetch::{FetchOptions, fetch_icons},
+        },
+        model::{Collection, GlyphOptions, PackIcon},
+    };
+
+    fn temp_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("icon-lock-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn icon(body: &str) -> IconifyIcon {
+        IconifyIcon {
+            body: body.to_string(),
+            width: Some(24.0),
+            height: Some(24.0),
+            transform: IconifyTransform::default(),
+        }
+    }
+
+    fn glyphs(names: &[&str]) -> BTreeMap<Collection, Vec<PackIcon>> {
+        let collection = Collection {
+            name: "mdi".to_string(),
+            local: false,
+        };
+        let packs = names
+            .iter()
+            .enumerate()
+            .map(|(order, name)| PackIcon {
+                name: name.to_string(),
+                enum_variant: name.to_string(),
+                icon: name.to_string(),
+                codepoint: 0,
+                pinned: None,
+                options: GlyphOptions::default(),
+                order,
+            })
+            .collect();
+        BTreeMap::from([(collection, packs)])
+    }
+
+    #[test]
+    fn edited_entries_are_rejected() {
+        let path = Path::new("icons.lock");
+        let mut lock = Lockfile::default();
+        lock.insert("mdi", "home", &icon("<path d=\"M0 0h24v24H0z\"/>"));
+        assert!(lock.get(path, "mdi", "home").unwrap().is_some());
+        assert!(lock.get(path, "mdi", "other").unwrap().is_none());
+
+        lock.icons.get_mut("mdi::home").unwrap().body = "<path d=\"M1 1\"/>".to_string();
+        let result = lock.get(path, "mdi", "home");
+        assert!(
+            matches!(&result, Err(Error::Lock { message, .. }) if message.contains("hash mismatch")),
+            "expected a hash mismatch, got {result:?}"
+        );
+    }
+
+    #[test]
+    fn present_lock_is_the_only_source() {
+        let lock_path = temp_path("icons.lock");
+        let cache_dir = temp_path("cache");
+        let pinned = icon("<path d=\"M0 0h24v24H0z\"/>");
+        let mut lock = Lockfile::default();
+        lock.insert("mdi", "home", &pinned);
+        lock.save(&lock_path).unwrap();
+        // A cached body that differs, which must not be picked up
+        IconCache::new(&cache_dir).put("mdi", "home", &icon("<circle r=\"4\"/>"));
+
+        // Nothing listens on the discard port, so any request would fail the build
+        let options = FetchOptions {
+            cache_dir: Some(cache_dir.clone()),
+            api_hosts: vec!["http://127.0.0.1:9".to_string()],
+            json_dir: None,
+            offline: false,
+            lock_path: lock_path.clone(),
+            refresh_lock: false,
+        };
+        let mut resolved = glyphs(&["home"]);
+        fetch_icons(&mut resolved, &options).unwrap();
+        let pack = &resolved.values().next().unwrap()[0];
+        assert_eq!(pack.icon, pinned.to_svg());
+        assert_eq!(Lockfile::load(&lock_path).unwrap(), lock);
+
+        // Icons the lock does not pin are not fetched either
+        let mut unpinned = glyphs(&["home", "account"]);
+        let result = fetch_icons(&mut unpinned, &options);
+        assert!(
+            matches!(&result, Err(Error::Lock { message, .. }) if message.contains("'mdi::account'")),
+            "expected the unpinned icon to fail, got {result:?}"
+        );
+
+        let _ = fs::remove_file(&lock_path);
+        let _ = fs::remove_dir_all(&cache_dir);
+    }
+}
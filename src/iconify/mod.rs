@@ -1,5 +1,9 @@
+mod cache;
 mod client;
 mod fetch;
+mod lock;
+mod render;
 mod types;
+mod vendored;
 
-pub(crate) use fetch::fetch_icons;
+pub(crate) use fetch::{FetchOptions, fetch_icons};
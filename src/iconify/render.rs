@@ -0,0 +1,205 @@
+use std::mem;
+
+use crate::{generator::font::wrap_iconify_svg, utils::fmt_number};
+
+use super::types::IconifyIcon;
+
+impl IconifyIcon {
+    /// Build a standalone SVG, applying the IconifyJSON transformations the same way
+    /// Iconify's `iconToSVG` does.
+    pub(crate) fn to_svg(&self) -> String {
+        let transform = &self.transform;
+        let mut left = transform.left.unwrap_or(0.0);
+        let mut top = transform.top.unwrap_or(0.0);
+        let mut width = self.width.unwrap_or_default();
+        let mut height = self.height.unwrap_or_default();
+        let mut rotation = transform.rotate.unwrap_or(0);
+
+        let mut transforms = Vec::new();
+        match (
+            transform.h_flip.unwrap_or(false),
+            transform.v_flip.unwrap_or(false),
+        ) {
+            // Flipping both ways is a half turn
+            (true, true) => rotation += 2,
+            (true, false) => {
+                transforms.push(format!(
+                    "translate({} {})",
+                    fmt_number(width + left),
+                    // `0.0 - top` avoids writing "-0"
+                    fmt_number(0.0 - top)
+                ));
+                transforms.push("scale(-1 1)".to_string());
+                left = 0.0;
+                top = 0.0;
+            }
+            (false, true) => {
+                transforms.push(format!(
+                    "translate({} {})",
+                    fmt_number(0.0 - left),
+                    fmt_number(height + top)
+                ));
+                transforms.push("scale(1 -1)".to_string());
+                left = 0.0;
+                top = 0.0;
+            }
+            (false, false) => {}
+        }
+
+        let rotation = rotation.rem_euclid(4);
+        match rotation {
+            1 => {
+                let center = fmt_number(height / 2.0 + top);
+                transforms.insert(0, format!("rotate(90 {center} {center})"));
+            }
+            2 => transforms.insert(
+                0,
+                format!(
+                    "rotate(180 {} {})",
+                    fmt_number(width / 2.0 + left),
+                    fmt_number(height / 2.0 + top)
+                ),
+            ),
+            3 => {
+                let center = fmt_number(width / 2.0 + left);
+                transforms.insert(0, format!("rotate(-90 {center} {center})"));
+            }
+            _ => {}
+        }
+
+        if rotation % 2 == 1 {
+            mem::swap(&mut left, &mut top);
+            mem::swap(&mut width, &mut height);
+        }
+
+        let body = match transforms.is_empty() {
+            true => self.body.clone(),
+            false => format!(
+                r#"<g transform="{}">{}</g>"#,
+                transforms.join(" "),
+                self.body.trim()
+            ),
+        };
+
+        wrap_iconify_svg(&body, left, top, width, height)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::iconify::types::IconifyTransform;
+
+    const BODY: &str = r#"<path d="M0 0h4v2H0z"/>"#;
+
+    /// A 24x16 icon, so turns that swap the sides show up in the viewBox.
+    fn icon(transform: IconifyTransform) -> IconifyIcon {
+        IconifyIcon {
+            body: BODY.to_string(),
+            width: Some(24.0),
+            height: Some(16.0),
+            transform,
+        }
+    }
+
+    /// The SVG around the body and viewBox `iconToSVG` returns.
+    fn expected(view_box: &str, transform: &str) -> String {
+        let body = match transform {
+            "" => BODY.to_string(),
+            _ => format!(r#"<g transform="{transform}">{BODY}</g>"#),
+        };
+        format!(r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{view_box}">{body}</svg>"#)
+    }
+
+    fn rotated(rotate: i32) -> IconifyTransform {
+        IconifyTransform {
+            rotate: Some(rotate),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn untransformed() {
+        let svg = icon(IconifyTransform::default()).to_svg();
+        assert_eq!(svg, expected("0 0 24 16", ""));
+    }
+
+    #[test]
+    fn rotations() {
+        assert_eq!(
+            icon(rotated(1)).to_svg(),
+            expected("0 0 16 24", "rotate(90 8 8)")
+        );
+        assert_eq!(
+            icon(rotated(2)).to_svg(),
+            expected("0 0 24 16", "rotate(180 12 8)")
+        );
+        assert_eq!(
+            icon(rotated(3)).to_svg(),
+            expected("0 0 16 24", "rotate(-90 12 12)")
+        );
+    }
+
+    #[test]
+    fn flips() {
+        let h_flip = IconifyTransform {
+            h_flip: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            icon(h_flip.clone()).to_svg(),
+            expected("0 0 24 16", "translate(24 0) scale(-1 1)")
+        );
+
+        let v_flip = IconifyTransform {
+            v_flip: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            icon(v_flip).to_svg(),
+            expected("0 0 24 16", "translate(0 16) scale(1 -1)")
+        );
+
+        // Both flips are a half turn
+        let both = IconifyTransform {
+            v_flip: Some(true),
+            ..h_flip
+        };
+        assert_eq!(
+            icon(both).to_svg(),
+            expected("0 0 24 16", "rotate(180 12 8)")
+        );
+    }
+
+    #[test]
+    fn offset_view_box() {
+        let offset = IconifyTransform {
+            left: Some(2.0),
+            top: Some(1.0),
+            ..Default::default()
+        };
+        assert_eq!(icon(offset.clone()).to_svg(), expected("2 1 24 16", ""));
+
+        let turned = IconifyTransform {
+            rotate: Some(1),
+            ..offset.clone()
+        };
+        assert_eq!(
+            icon(turned).to_svg(),
+            expected("1 2 16 24", "rotate(90 9 9)")
+        );
+
+        let flipped = IconifyTransform {
+            h_flip: Some(true),
+            rotate: Some(3),
+            ..offset
+        };
+        assert_eq!(
+            icon(flipped).to_svg(),
+            expected(
+                "0 0 16 24",
+                "rotate(-90 12 12) translate(26 -1) scale(-1 1)"
+            )
+        );
+    }
+}
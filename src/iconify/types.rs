@@ -1,18 +1,235 @@
 use std::collections::BTreeMap;
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
+/// Iconify's default icon size when a collection does not set one.
+const DEFAULT_SIZE: f64 = 16.0;
+
+/// Alias chains longer than this are treated as broken (mirrors Iconify's own limit).
+const MAX_ALIAS_DEPTH: usize = 24;
+
+fn default_size() -> f64 {
+    DEFAULT_SIZE
+}
+
+/// Collection data as returned by the API or stored in `@iconify/json` files.
 #[derive(Deserialize)]
 pub(crate) struct IconifyResponse {
     pub prefix: String,
     pub icons: BTreeMap<String, IconifyIcon>,
+    #[serde(default)]
+    pub aliases: BTreeMap<String, IconifyAlias>,
+    #[serde(default = "default_size")]
     pub width: f64,
+    #[serde(default = "default_size")]
     pub height: f64,
+    #[serde(flatten)]
+    pub transform: IconifyTransform,
+    /// Requested names the API does not know.
+    #[serde(default)]
+    pub not_found: Vec<String>,
 }
 
-#[derive(Deserialize)]
+impl IconifyResponse {
+    /// Look up an icon or alias with the collection defaults applied.
+    pub(crate) fn icon(&self, name: &str) -> Option<IconifyIcon> {
+        let mut width = None;
+        let mut height = None;
+        let mut aliased = IconifyTransform::default();
+        let mut name = name;
+
+        for _ in 0..=MAX_ALIAS_DEPTH {
+            if let Some(icon) = self.icons.get(name) {
+                // Collection defaults are merged like a parent, so their flips toggle too
+                let transform = self.transform.merge(&icon.transform);
+                return Some(IconifyIcon {
+                    body: icon.body.clone(),
+                    width: Some(width.or(icon.width).unwrap_or(self.width)),
+                    height: Some(height.or(icon.height).unwrap_or(self.height)),
+                    transform: transform.merge(&aliased),
+                });
+            }
+
+            // The alias closest to the requested name wins
+            let alias = self.aliases.get(name)?;
+            width = width.or(alias.width);
+            height = height.or(alias.height);
+            aliased = alias.transform.merge(&aliased);
+            name = &alias.parent;
+        }
+
+        None
+    }
+
+    /// Every icon and alias name in the response.
+    pub(crate) fn names(&self) -> impl Iterator<Item = &str> + Clone {
+        self.icons
+            .keys()
+            .chain(self.aliases.keys())
+            .map(String::as_str)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub(crate) struct IconifyIcon {
     pub body: String,
     pub width: Option<f64>,
     pub height: Option<f64>,
+    #[serde(flatten)]
+    pub transform: IconifyTransform,
+}
+
+/// Optional IconifyJSON icon properties that move or turn the body.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct IconifyTransform {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub left: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top: Option<f64>,
+    /// Quarter turns clockwise.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rotate: Option<i32>,
+    #[serde(default, rename = "hFlip", skip_serializing_if = "Option::is_none")]
+    pub h_flip: Option<bool>,
+    #[serde(default, rename = "vFlip", skip_serializing_if = "Option::is_none")]
+    pub v_flip: Option<bool>,
+}
+
+impl IconifyTransform {
+    /// Apply `child` on top of this transform the way Iconify merges an alias onto
+    /// its parent: offsets are replaced, rotations add up and flips toggle.
+    pub(crate) fn merge(&self, child: &IconifyTransform) -> IconifyTransform {
+        fn toggle(parent: Option<bool>, child: Option<bool>) -> Option<bool> {
+            match (parent, child) {
+                (None, None) => None,
+                (parent, child) => Some(parent.unwrap_or(false) != child.unwrap_or(false)),
+            }
+        }
+
+        let rotate = match (self.rotate, child.rotate) {
+            (None, None) => None,
+            (parent, child) => Some((parent.unwrap_or(0) + child.unwrap_or(0)).rem_euclid(4)),
+        };
+
+        IconifyTransform {
+            left: child.left.or(self.left),
+            top: child.top.or(self.top),
+            rotate,
+            h_flip: toggle(self.h_flip, child.h_flip),
+            v_flip: toggle(self.v_flip, child.v_flip),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct IconifyAlias {
+    pub parent: String,
+    pub width: Option<f64>,
+    pub height: Option<f64>,
+    #[serde(flatten)]
+    pub transform: IconifyTransform,
+}
+
+/// Icon listing returned by the API's `/collection` endpoint.
+#[derive(Deserialize)]
+pub(crate) struct IconifyCollectionInfo {
+    #[serde(default)]
+    pub uncategorized: Vec<String>,
+    #[serde(default)]
+    pub categories: BTreeMap<String, Vec<String>>,
+    #[serde(default)]
+    pub hidden: Vec<String>,
+    #[serde(default)]
+    pub aliases: BTreeMap<String, String>,
+}
+
+impl IconifyCollectionInfo {
+    pub(crate) fn into_names(self) -> Vec<String> {
+        let mut names = self.uncategorized;
+        names.extend(self.categories.into_values().flatten());
+        names.extend(self.hidden);
+        names.extend(self.aliases.into_keys());
+        names.sort();
+        names.dedup();
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn collection(json: &str) -> IconifyResponse {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn alias_chain_merges_transforms() {
+        let response = collection(
+            r#"{
+                "prefix": "test",
+                "width": 24,
+                "height": 24,
+                "icons": {"base": {"body": "<path/>", "hFlip": true, "width": 32}},
+                "aliases": {
+                    "turned": {"parent": "base", "rotate": 1, "hFlip": true},
+                    "wide": {"parent": "turned", "rotate": 2, "vFlip": true, "width": 40}
+                }
+            }"#,
+        );
+
+        let turned = response.icon("turned").unwrap();
+        assert_eq!(turned.width, Some(32.0));
+        assert_eq!(turned.transform.rotate, Some(1));
+        assert_eq!(turned.transform.h_flip, Some(false));
+
+        // Rotations add up and flips toggle along the whole chain
+        let wide = response.icon("wide").unwrap();
+        assert_eq!((wide.width, wide.height), (Some(40.0), Some(24.0)));
+        assert_eq!(wide.transform.rotate, Some(3));
+        assert_eq!(wide.transform.h_flip, Some(false));
+        assert_eq!(wide.transform.v_flip, Some(true));
+        assert_eq!(
+            wide.to_svg(),
+            r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 24 40"><g transform="rotate(-90 20 20) translate(0 24) scale(1 -1)"><path/></g></svg>"#
+        );
+
+        assert!(response.icon("missing").is_none());
+    }
+
+    #[test]
+    fn collection_transform_merges_with_the_icon() {
+        let response = collection(
+            r#"{
+                "prefix": "test",
+                "hFlip": true,
+                "rotate": 1,
+                "icons": {
+                    "flipped": {"body": "<path/>", "hFlip": true, "rotate": 1},
+                    "plain": {"body": "<path/>"}
+                }
+            }"#,
+        );
+
+        let flipped = response.icon("flipped").unwrap();
+        assert_eq!(flipped.transform.h_flip, Some(false));
+        assert_eq!(flipped.transform.rotate, Some(2));
+        assert_eq!((flipped.width, flipped.height), (Some(16.0), Some(16.0)));
+
+        let plain = response.icon("plain").unwrap();
+        assert_eq!(plain.transform.h_flip, Some(true));
+        assert_eq!(plain.transform.rotate, Some(1));
+    }
+
+    #[test]
+    fn alias_loops_are_broken() {
+        let response = collection(
+            r#"{
+                "prefix": "test",
+                "icons": {},
+                "aliases": {"a": {"parent": "b"}, "b": {"parent": "a"}}
+            }"#,
+        );
+        assert!(response.icon("a").is_none());
+    }
 }
@@ -0,0 +1,35 @@
+use std::{fs, path::Path};
+
+use crate::{Error, model::Collection};
+
+use super::types::IconifyResponse;
+
+/// Load `<prefix>.json` from a vendored `@iconify/json` directory, `None` when the
+/// collection is not vendored.
+pub(crate) fn load_collection(
+    dir: &Path,
+    collection: &Collection,
+) -> Result<Option<IconifyResponse>, Error> {
+    let path = dir.join(&collection.name).with_extension("json");
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let collection_error = |message| Error::Collection {
+        collection: collection.name.clone(),
+        path: path.clone(),
+        message,
+    };
+    let content = fs::read(&path).map_err(|err| collection_error(err.to_string()))?;
+    let parsed: IconifyResponse = serde_json::from_slice(&content)
+        .map_err(|err| collection_error(format!("invalid IconifyJSON: {err}")))?;
+
+    if parsed.prefix != collection.name {
+        return Err(Error::PrefixMismatch {
+            collection: collection.name.clone(),
+            received: parsed.prefix,
+        });
+    }
+
+    Ok(Some(parsed))
+}
@@ -1,30 +1,40 @@
 mod config;
+mod error;
 mod generator;
 mod iconify;
 mod model;
 mod utils;
 
-pub use model::GenType;
+pub use error::{Error, MissingIcon};
+pub use model::{BuildReport, GenType};
 
 use crate::config::parse_definition;
-use crate::generator::{font_path, generate_font};
-use crate::iconify::fetch_icons;
+use crate::generator::{CodepointMap, embedded_hash, font_path, generate_font, static_font_path};
+use crate::iconify::{FetchOptions, fetch_icons};
 use crate::model::Definition;
 use crate::utils::{
-    extract_hash, glyphs_in_order, module_file_path, relative_path, upper_first_char,
+    env_flag, extract_hash, glyphs_in_order, hash_with_files, module_file_path, relative_path,
+    upper_first_char,
 };
 use handlebars::Handlebars;
 use serde_json::json;
 use std::{
-    fs,
+    env, fs,
     path::{Path, PathBuf},
 };
 
-const ICED_TEMPLATE: &str = include_str!("../templates/iced.rs.hbs");
+pub(crate) const ICED_TEMPLATE: &str = include_str!("../templates/iced.rs.hbs");
 
 pub struct Icon {
     path: PathBuf,
     assets_path: Option<PathBuf>,
+    cache_path: Option<PathBuf>,
+    iconify_json_path: Option<PathBuf>,
+    iconify_api: Option<String>,
+    iconify_mirrors: Option<Vec<String>>,
+    offline: bool,
+    refresh_lock: bool,
+    compact_codepoints: bool,
 
     gen_type: GenType,
     definition: Definition,
@@ -37,6 +47,13 @@ impl Icon {
         Icon {
             path,
             assets_path: None,
+            cache_path: None,
+            iconify_json_path: None,
+            iconify_api: None,
+            iconify_mirrors: None,
+            offline: false,
+            refresh_lock: false,
+            compact_codepoints: false,
             gen_type: GenType::Font,
             definition: Default::default(),
             hash: Default::default(),
@@ -48,64 +65,264 @@ impl Icon {
         self
     }
 
+    /// Directory for cached Iconify icons, defaults to `$OUT_DIR/icon-cache` in build scripts.
+    pub fn set_cache_path(&mut self, cache_path: impl AsRef<Path>) -> &mut Self {
+        self.cache_path = Some(cache_path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Directory of vendored `@iconify/json` files (`<prefix>.json`), preferred over the API.
+    pub fn set_iconify_json_path(&mut self, json_path: impl AsRef<Path>) -> &mut Self {
+        self.iconify_json_path = Some(json_path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Iconify API base url, overrides `api` in the `[iconify]` table of the definition.
+    pub fn set_iconify_api(&mut self, url: impl Into<String>) -> &mut Self {
+        self.iconify_api = Some(url.into());
+        self
+    }
+
+    /// Fallback API urls tried in order, overrides `mirrors` in the `[iconify]` table.
+    pub fn set_iconify_mirrors<I, S>(&mut self, urls: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.iconify_mirrors = Some(urls.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Resolve Iconify icons from the cache only; also enabled by `ICON_OFFLINE=1`.
+    pub fn set_offline(&mut self, offline: bool) -> &mut Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Re-resolve every Iconify icon instead of using the pinned bodies in `icons.lock`,
+    /// needed to add new icons once the lock exists; also enabled by `ICON_REFRESH_LOCK=1`.
+    pub fn refresh_lock(&mut self) -> &mut Self {
+        self.refresh_lock = true;
+        self
+    }
+
+    /// Renumber all glyphs from the start of the codepoint range, closing the holes left
+    /// by removed glyphs; also enabled by `ICON_COMPACT_CODEPOINTS=1`.
+    pub fn compact_codepoints(&mut self) -> &mut Self {
+        self.compact_codepoints = true;
+        self
+    }
+
     pub fn set_gen_type(&mut self, gen_type: GenType) -> &mut Self {
         self.gen_type = gen_type;
         self
     }
 
+    /// Build the font (and module), panicking with the error message on failure.
     pub fn build(&mut self) {
-        let (definition, hash) = parse_definition(&self.path, self.assets_path.as_deref());
+        if let Err(err) = self.try_build() {
+            panic!("{err}");
+        }
+    }
+
+    pub fn try_build(&mut self) -> Result<BuildReport, Error> {
+        let (definition, source_hash) = parse_definition(&self.path, self.assets_path.as_deref())?;
         self.definition = definition;
-        self.hash = hash;
+        self.hash = self.hash_with_side_files(&source_hash);
+        self.track_sources();
 
-        if self.up_to_date() {
-            return;
+        let options = self.fetch_options();
+        let compact = self.compact_codepoints || env_flag("ICON_COMPACT_CODEPOINTS");
+        let up_to_date = !options.refresh_lock && !compact && self.up_to_date();
+        if !up_to_date {
+            fetch_icons(&mut self.definition.glyphs, &options)?;
+
+            let codepoints_path = self.codepoints_path();
+            let mut codepoints = CodepointMap::load(&codepoints_path)?;
+            codepoints.assign(
+                &codepoints_path,
+                &mut self.definition.glyphs,
+                &self.definition.codepoints,
+                compact,
+            )?;
+            codepoints.save(&codepoints_path)?;
+
+            // The lockfile and codepoint map may have been created or updated above
+            self.hash = self.hash_with_side_files(&source_hash);
+            generate_font(
+                &self.path,
+                &self.definition.module,
+                &self.hash,
+                &self.definition.font,
+                &mut self.definition.glyphs,
+            )?;
+
+            match self.gen_type {
+                GenType::Font => (),
+                GenType::Iced => self.generate_iced()?,
+            }
         }
 
-        fetch_icons(&mut self.definition.glyphs);
-        generate_font(
+        let (font_path, _) = font_path(
             &self.path,
             &self.definition.module,
-            &mut self.definition.glyphs,
+            self.definition.font.main_format(),
         );
+        let font_paths = self.font_paths();
+        let module_path = matches!(self.gen_type, GenType::Iced)
+            .then(|| module_file_path("src", &self.definition.module));
+
+        Ok(BuildReport {
+            up_to_date,
+            font_path,
+            font_paths,
+            module_path,
+            glyph_count: self.definition.glyphs.values().map(Vec::len).sum(),
+            hash: self.hash.clone(),
+        })
+    }
+
+    fn fetch_options(&self) -> FetchOptions {
+        let cache_dir = self.cache_path.clone().or_else(|| {
+            env::var_os("OUT_DIR").map(|out_dir| PathBuf::from(out_dir).join("icon-cache"))
+        });
 
-        match self.gen_type {
-            GenType::Font => (),
-            GenType::Iced => self.generate_iced(),
+        let mut iconify = self.definition.iconify.clone();
+        if let Some(api) = &self.iconify_api {
+            iconify.api = Some(api.clone());
+        }
+        if let Some(mirrors) = &self.iconify_mirrors {
+            iconify.mirrors = mirrors.clone();
         }
+
+        FetchOptions {
+            cache_dir,
+            api_hosts: iconify.hosts(),
+            json_dir: self.iconify_json_path.clone(),
+            offline: self.offline || env_flag("ICON_OFFLINE"),
+            lock_path: self.lock_path(),
+            refresh_lock: self.refresh_lock || env_flag("ICON_REFRESH_LOCK"),
+        }
+    }
+
+    fn lock_path(&self) -> PathBuf {
+        self.path.with_extension("lock")
+    }
+
+    fn codepoints_path(&self) -> PathBuf {
+        self.path.with_extension("codepoints")
+    }
+
+    fn hash_with_side_files(&self, source_hash: &str) -> String {
+        hash_with_files(source_hash, &[&self.lock_path(), &self.codepoints_path()])
+    }
+
+    /// Ask cargo to rerun the build script when any input of the definition or one of the
+    /// `ICON_*` flags changes.
+    ///
+    /// Printing `rerun-if-changed` replaces cargo's default of rerunning on any change in
+    /// the package, so a build script with other inputs has to list them too.
+    fn track_sources(&self) {
+        if env::var_os("OUT_DIR").is_none() {
+            return;
+        }
+
+        let lock_path = self.lock_path();
+        let codepoints_path = self.codepoints_path();
+        let sources = [&self.path, &lock_path, &codepoints_path].into_iter();
+        for source in sources.chain(&self.definition.assets) {
+            println!("cargo::rerun-if-changed={}", source.display());
+        }
+        for flag in [
+            "ICON_OFFLINE",
+            "ICON_REFRESH_LOCK",
+            "ICON_COMPACT_CODEPOINTS",
+        ] {
+            println!("cargo::rerun-if-env-changed={flag}");
+        }
+    }
+
+    /// Every font file the definition asks for, static weights after the regular ones.
+    fn font_paths(&self) -> Vec<PathBuf> {
+        let font = &self.definition.font;
+        let module = &self.definition.module;
+        let regular = font
+            .formats
+            .iter()
+            .map(|&format| font_path(&self.path, module, format).0);
+        let weights = font.static_weights().flat_map(|(name, _)| {
+            font.formats
+                .iter()
+                .map(move |&format| static_font_path(&self.path, module, name, format))
+        });
+        regular.chain(weights).collect()
     }
 
     fn up_to_date(&mut self) -> bool {
-        let (font_path, _) = font_path(&self.path, &self.definition.module);
-        if !font_path.exists() {
+        if !self.font_paths().iter().all(|path| path.exists()) {
             return false;
         }
 
-        if matches!(self.gen_type, GenType::Font) {
-            return true;
+        let (font_path, _) = font_path(
+            &self.path,
+            &self.definition.module,
+            self.definition.font.main_format(),
+        );
+        // A module is only current together with the font it embeds
+        let font_current =
+            matches!(embedded_hash(&font_path), Some(existing) if existing == self.hash);
+        if !font_current || matches!(self.gen_type, GenType::Font) {
+            return font_current;
         }
 
         let module_path = module_file_path("src", &self.definition.module);
         matches!(extract_hash(&module_path), Some(existing) if existing == self.hash)
     }
 
-    fn generate_iced(&mut self) {
+    fn generate_iced(&mut self) -> Result<(), Error> {
         let module_path = module_file_path("src", &self.definition.module);
-        let (font_file_path, module_basename) = font_path(&self.path, &self.definition.module);
+        let (font_file_path, module_basename) = font_path(
+            &self.path,
+            &self.definition.module,
+            self.definition.font.main_format(),
+        );
         if !font_file_path.exists() {
-            panic!(
-                "font file '{}' missing; run build with GenType::Font at least once",
-                font_file_path.display()
-            );
+            return Err(Error::Config {
+                path: self.path.clone(),
+                message: format!(
+                    "font file '{}' missing; run build with GenType::Font at least once",
+                    font_file_path.display()
+                ),
+            });
         }
 
         let module_dir = module_path.parent().unwrap_or_else(|| Path::new(""));
         let module = upper_first_char(&module_basename);
 
-        let font_include = relative_path(module_dir, &font_file_path)
-            .display()
-            .to_string()
-            .replace('\\', "/");
+        let include = |path: &Path| {
+            relative_path(module_dir, path)
+                .display()
+                .to_string()
+                .replace('\\', "/")
+        };
+        let font_include = include(&font_file_path);
+        let weights = self
+            .definition
+            .font
+            .static_weights()
+            .map(|(name, _)| {
+                let path = static_font_path(
+                    &self.path,
+                    &self.definition.module,
+                    name,
+                    self.definition.font.main_format(),
+                );
+                json!({
+                    "constant": name.to_ascii_uppercase().replace('-', "_"),
+                    "font_include": include(&path),
+                })
+            })
+            .collect::<Vec<_>>();
         let font_name = font_file_path
             .file_stem()
             .expect("font file path missing file stem")
@@ -119,26 +336,28 @@ impl Icon {
                 let pack = glyphs
                     .get(&collection)
                     .and_then(|packs| packs.get(index))
-                    .unwrap_or_else(|| {
-                        panic!("glyph order mismatch for collection '{}'", collection.name)
-                    });
-
-                let ch = pack
-                    .icon
-                    .chars()
-                    .next()
-                    .expect("icon missing generated codepoint");
-                json!({
+                    .ok_or_else(|| Error::Template {
+                        message: format!(
+                            "glyph order mismatch for collection '{}'",
+                            collection.name
+                        ),
+                    })?;
+
+                Ok(json!({
                     "variant": pack.enum_variant,
-                    "codepoint": format!("\\u{{{:04X}}}", ch as u32),
-                })
+                    "codepoint": format!("\\u{{{:04X}}}", pack.codepoint),
+                }))
             })
-            .collect::<Vec<_>>();
+            .collect::<Result<Vec<_>, Error>>()?;
 
+        // A variable font takes the weight as well, without files of its own
+        let weighted = self.definition.font.variable || !weights.is_empty();
         let data = json!({
             "module": module,
             "font_include": font_include,
             "font_name": font_name,
+            "weights": weights,
+            "weighted": weighted,
             "icon_hash": &self.hash,
             "icons": icons,
         });
@@ -146,14 +365,22 @@ impl Icon {
         let handlebars = Handlebars::new();
         let rendered = handlebars
             .render_template(ICED_TEMPLATE, &data)
-            .expect("failed to render Iced template");
+            .map_err(|err| Error::Template {
+                message: err.to_string(),
+            })?;
 
         if let Some(parent) = module_path.parent()
             && !parent.as_os_str().is_empty()
         {
-            fs::create_dir_all(parent).expect("failed to create module directories");
+            fs::create_dir_all(parent).map_err(|source| Error::Io {
+                path: parent.to_path_buf(),
+                source,
+            })?;
         }
 
-        fs::write(&module_path, rendered).expect("failed to write generated Iced module");
+        fs::write(&module_path, rendered).map_err(|source| Error::Io {
+            path: module_path,
+            source,
+        })
     }
 }
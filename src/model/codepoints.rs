@@ -0,0 +1,62 @@
+use std::ops::RangeInclusive;
+
+use serde::{Deserialize, Serialize};
+
+/// Private Use Area the glyph codepoints are taken from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum CodepointRange {
+    /// `U+E000..U+F8FF` in the Basic Multilingual Plane.
+    #[default]
+    Bmp,
+    /// Supplementary Private Use Area-A, `U+F0000..U+FFFFD`.
+    PuaA,
+    /// Supplementary Private Use Area-B, `U+100000..U+10FFFD`.
+    PuaB,
+}
+
+impl CodepointRange {
+    const ALL: [CodepointRange; 3] = [Self::Bmp, Self::PuaA, Self::PuaB];
+
+    pub(crate) fn bounds(self) -> RangeInclusive<u32> {
+        match self {
+            CodepointRange::Bmp => 0xE000..=0xF8FF,
+            CodepointRange::PuaA => 0xF0000..=0xFFFFD,
+            CodepointRange::PuaB => 0x100000..=0x10FFFD,
+        }
+    }
+
+    /// `true` for a codepoint in any of the Private Use Areas.
+    pub(crate) fn is_private_use(codepoint: u32) -> bool {
+        Self::ALL
+            .iter()
+            .any(|range| range.bounds().contains(&codepoint))
+    }
+}
+
+/// `[codepoints]` table of the definition.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct CodepointConfig {
+    #[serde(default)]
+    pub range: CodepointRange,
+    /// First codepoint handed out, defaults to the start of `range`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<u32>,
+}
+
+impl CodepointConfig {
+    pub(crate) fn start(&self) -> u32 {
+        self.start.unwrap_or(*self.range.bounds().start())
+    }
+
+    pub(crate) fn end(&self) -> u32 {
+        *self.range.bounds().end()
+    }
+
+    /// Describe the range for error messages, e.g. `U+E000..U+F8FF`.
+    pub(crate) fn describe(&self) -> String {
+        let bounds = self.range.bounds();
+        format!("U+{:04X}..U+{:04X}", bounds.start(), bounds.end())
+    }
+}
@@ -1,9 +1,14 @@
-use std::collections::BTreeMap;
+use std::{collections::BTreeMap, path::PathBuf};
 
-use super::{Collection, PackIcon};
+use super::{CodepointConfig, Collection, FontConfig, IconifyConfig, PackIcon};
 
 #[derive(Debug, Clone, Default)]
 pub struct Definition {
     pub module: String,
     pub glyphs: BTreeMap<Collection, Vec<PackIcon>>,
+    /// Resolved local asset files, in definition order.
+    pub assets: Vec<PathBuf>,
+    pub iconify: IconifyConfig,
+    pub font: FontConfig,
+    pub codepoints: CodepointConfig,
 }
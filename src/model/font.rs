@@ -0,0 +1,203 @@
+use std::fmt;
+
+use indexmap::IndexMap;
+use serde::{Deserialize, Serialize};
+
+/// Font file formats that can be written for a definition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum FontFormat {
+    Ttf,
+    /// OpenType with `CFF ` outlines, keeping the cubic curves of the SVGs.
+    Otf,
+    Woff,
+    Woff2,
+}
+
+impl FontFormat {
+    pub(crate) fn extension(self) -> &'static str {
+        match self {
+            FontFormat::Ttf => "ttf",
+            FontFormat::Otf => "otf",
+            FontFormat::Woff => "woff",
+            FontFormat::Woff2 => "woff2",
+        }
+    }
+}
+
+/// Where icons sit relative to the baseline of surrounding text.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum VerticalAlign {
+    /// The em box starts at the baseline, like the glyphs of a cap-height font.
+    #[default]
+    Baseline,
+    /// The em box is centered around the middle of lowercase text, reaching below the baseline.
+    Center,
+}
+
+/// A color painted by a color glyph layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) enum PaletteColor {
+    /// The text color, `currentColor` in SVG.
+    Foreground,
+    /// An sRGB color with straight alpha.
+    Rgba([u8; 4]),
+}
+
+impl PaletteColor {
+    /// Parse `currentColor`, `#RGB`, `#RRGGBB` or `#RRGGBBAA`.
+    pub(crate) fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("currentColor") {
+            return Some(PaletteColor::Foreground);
+        }
+
+        let hex = value.strip_prefix('#')?;
+        if !hex.is_ascii() {
+            return None;
+        }
+        let channel = |at: usize, len: usize| {
+            let digits = &hex[at..at + len];
+            u8::from_str_radix(digits, 16)
+                .ok()
+                .map(|value| if len == 1 { value * 17 } else { value })
+        };
+        let rgba = match hex.len() {
+            3 => [channel(0, 1)?, channel(1, 1)?, channel(2, 1)?, 255],
+            6 => [channel(0, 2)?, channel(2, 2)?, channel(4, 2)?, 255],
+            8 => [
+                channel(0, 2)?,
+                channel(2, 2)?,
+                channel(4, 2)?,
+                channel(6, 2)?,
+            ],
+            _ => return None,
+        };
+        Some(PaletteColor::Rgba(rgba))
+    }
+}
+
+impl fmt::Display for PaletteColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaletteColor::Foreground => f.write_str("currentColor"),
+            PaletteColor::Rgba([r, g, b, 255]) => write!(f, "#{r:02X}{g:02X}{b:02X}"),
+            PaletteColor::Rgba([r, g, b, a]) => write!(f, "#{r:02X}{g:02X}{b:02X}{a:02X}"),
+        }
+    }
+}
+
+impl TryFrom<String> for PaletteColor {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        PaletteColor::parse(&value).ok_or_else(|| {
+            format!("invalid color '{value}', expected '#RRGGBB', '#RRGGBBAA' or 'currentColor'")
+        })
+    }
+}
+
+impl From<PaletteColor> for String {
+    fn from(color: PaletteColor) -> Self {
+        color.to_string()
+    }
+}
+
+/// `wght` of the icons as drawn in the SVGs.
+pub(crate) const REGULAR_WEIGHT: u16 = 400;
+
+/// The weight style linking treats as the bold of the regular font.
+pub(crate) const BOLD_WEIGHT: u16 = 700;
+
+/// A named weight of the icons, drawn by scaling the stroke widths of the SVGs.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) struct FontWeight {
+    /// Value on the `wght` axis, 1 to 1000.
+    pub weight: u16,
+    /// Factor applied to every stroke width; fills stay as drawn.
+    pub stroke: f64,
+}
+
+fn default_formats() -> Vec<FontFormat> {
+    vec![FontFormat::Ttf]
+}
+
+/// `[font]` table of the definition.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct FontConfig {
+    /// Output files to write; the web formats wrap the same tables as the main font.
+    #[serde(default = "default_formats")]
+    pub formats: Vec<FontFormat>,
+    /// Add a `liga` feature so typing a glyph's key shows the icon.
+    #[serde(default)]
+    pub ligatures: bool,
+    /// Give each glyph an advance following its aspect ratio instead of a square em.
+    #[serde(default)]
+    pub proportional: bool,
+    #[serde(default)]
+    pub align: VerticalAlign,
+    /// Union overlapping contours, such as strokes crossing fills, into one clean outline.
+    #[serde(default)]
+    pub remove_overlaps: bool,
+    /// Keep the colors of the SVGs as `COLR`/`CPAL` layers over the monochrome glyph.
+    #[serde(default)]
+    pub color: bool,
+    /// Paint gradients with `COLR` version 1 instead of their average color.
+    #[serde(default)]
+    pub gradients: bool,
+    /// Embed every icon as an SVG document in an `SVG ` table, the outlines as fallback.
+    #[serde(default)]
+    pub svg: bool,
+    /// Colors of the SVGs to paint differently in color glyphs.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub palette: IndexMap<PaletteColor, PaletteColor>,
+    /// Weights besides the regular one, keyed by the name of their instance.
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub weights: IndexMap<String, FontWeight>,
+    /// Write the TTF as a variable font with a `wght` axis through `weights` instead of a
+    /// static font per weight.
+    #[serde(default)]
+    pub variable: bool,
+}
+
+impl Default for FontConfig {
+    fn default() -> Self {
+        Self {
+            formats: default_formats(),
+            ligatures: false,
+            proportional: false,
+            align: VerticalAlign::Baseline,
+            remove_overlaps: false,
+            color: false,
+            gradients: false,
+            svg: false,
+            palette: IndexMap::new(),
+            weights: IndexMap::new(),
+            variable: false,
+        }
+    }
+}
+
+impl FontConfig {
+    /// The font applications load: the TTF when requested, otherwise the OTF.
+    pub(crate) fn main_format(&self) -> FontFormat {
+        match self.formats.contains(&FontFormat::Ttf) {
+            true => FontFormat::Ttf,
+            false => FontFormat::Otf,
+        }
+    }
+
+    /// Weights written as fonts of their own, all of them unless the font is variable.
+    pub(crate) fn static_weights(&self) -> impl Iterator<Item = (&str, &FontWeight)> {
+        let weights = match self.variable {
+            true => None,
+            false => Some(&self.weights),
+        };
+        weights
+            .into_iter()
+            .flatten()
+            .map(|(name, weight)| (name.as_str(), weight))
+    }
+}
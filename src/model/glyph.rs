@@ -1,6 +1,59 @@
+/// Optical adjustments applied to an icon before it is scaled into the em box.
+///
+/// Lengths are fractions of the icon box (its viewBox), so `0.1` is 10% of the icon.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlyphTransform {
+    pub scale: f64,
+    /// Moves the icon right.
+    pub offset_x: f64,
+    /// Moves the icon up.
+    pub offset_y: f64,
+    /// Clockwise rotation in degrees.
+    pub rotate: f64,
+    pub flip_h: bool,
+    pub flip_v: bool,
+    /// Empty space added around the icon box on every side.
+    pub padding: f64,
+}
+
+impl Default for GlyphTransform {
+    fn default() -> Self {
+        Self {
+            scale: 1.0,
+            offset_x: 0.0,
+            offset_y: 0.0,
+            rotate: 0.0,
+            flip_h: false,
+            flip_v: false,
+            padding: 0.0,
+        }
+    }
+}
+
+impl GlyphTransform {
+    pub fn is_identity(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Per-glyph overrides from the table form of a glyph entry.
+#[derive(Debug, Clone, Default)]
+pub struct GlyphOptions {
+    /// Overrides `proportional` of the `[font]` table.
+    pub proportional: Option<bool>,
+    pub transform: GlyphTransform,
+}
+
 #[derive(Debug, Clone)]
 pub struct PackIcon {
+    /// Key of the entry in the definition, also used as its ligature.
+    pub name: String,
     pub enum_variant: String,
     pub icon: String,
+    /// Assigned from the codepoint map right before the font is generated.
+    pub codepoint: u32,
+    /// Codepoint fixed by the definition, kept regardless of the codepoint map.
+    pub pinned: Option<u32>,
+    pub options: GlyphOptions,
     pub order: usize,
 }
@@ -0,0 +1,23 @@
+use serde::Deserialize;
+
+/// The public Iconify API, used unless the definition or builder names another host.
+pub(crate) const DEFAULT_ICONIFY_API: &str = "https://api.iconify.design";
+
+/// `[iconify]` table of the definition.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub(crate) struct IconifyConfig {
+    pub api: Option<String>,
+    /// Tried in order when the primary API fails.
+    #[serde(default)]
+    pub mirrors: Vec<String>,
+}
+
+impl IconifyConfig {
+    /// Primary API followed by the mirrors.
+    pub(crate) fn hosts(&self) -> Vec<String> {
+        let api = self.api.as_deref().unwrap_or(DEFAULT_ICONIFY_API);
+        let mut hosts = vec![api.to_string()];
+        hosts.extend(self.mirrors.iter().cloned());
+        hosts
+    }
+}
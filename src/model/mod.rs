@@ -1,9 +1,19 @@
+mod codepoints;
 mod collection;
 mod definition;
+mod font;
 mod gen_type;
 mod glyph;
+mod iconify;
+mod report;
 
+pub(crate) use codepoints::{CodepointConfig, CodepointRange};
 pub(crate) use collection::Collection;
 pub(crate) use definition::Definition;
+pub(crate) use font::{
+    BOLD_WEIGHT, FontConfig, FontFormat, FontWeight, PaletteColor, REGULAR_WEIGHT, VerticalAlign,
+};
 pub use gen_type::GenType;
-pub(crate) use glyph::PackIcon;
+pub(crate) use glyph::{GlyphOptions, GlyphTransform, PackIcon};
+pub(crate) use iconify::IconifyConfig;
+pub use report::BuildReport;
@@ -0,0 +1,17 @@
+use std::path::PathBuf;
+
+/// Summary of a successful [`Icon::try_build`](crate::Icon::try_build) run.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct BuildReport {
+    /// `true` when the existing outputs matched the definition and nothing was written.
+    pub up_to_date: bool,
+    /// Path of the main font, the TTF or else the OTF, which the Iced module embeds.
+    pub font_path: PathBuf,
+    /// Every font file written for the definition's `formats`, the main font included.
+    pub font_paths: Vec<PathBuf>,
+    /// Path of the generated module, only set for [`GenType::Iced`](crate::GenType::Iced).
+    pub module_path: Option<PathBuf>,
+    pub glyph_count: usize,
+    pub hash: String,
+}
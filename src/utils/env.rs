@@ -0,0 +1,6 @@
+use std::env;
+
+/// `true` when the variable is set to `1` or `true`.
+pub(crate) fn env_flag(name: &str) -> bool {
+    env::var(name).is_ok_and(|value| matches!(value.trim(), "1" | "true"))
+}
@@ -1,3 +1,4 @@
+use sha2::{Digest, Sha256};
 use std::{fs, path::Path};
 
 pub(crate) fn extract_hash(path: &Path) -> Option<String> {
@@ -20,3 +21,22 @@ pub(crate) fn hex_upper(bytes: impl AsRef<[u8]>) -> String {
     }
     out
 }
+
+/// Fold the contents of generated side files (lockfiles) into a definition hash.
+/// Missing files are skipped, so the hash is unchanged when none exist.
+pub(crate) fn hash_with_files(hash: &str, files: &[&Path]) -> String {
+    let contents: Vec<_> = files
+        .iter()
+        .filter_map(|file| fs::read(file).ok())
+        .collect();
+    if contents.is_empty() {
+        return hash.to_string();
+    }
+
+    let mut hasher = Sha256::new();
+    hasher.update(hash);
+    for content in contents {
+        hasher.update(content);
+    }
+    hex_upper(hasher.finalize())
+}
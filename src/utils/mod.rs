@@ -1,9 +1,11 @@
+pub mod env;
 pub mod glyphs;
 pub mod hash;
 pub mod paths;
 pub mod strings;
 
+pub(crate) use env::env_flag;
 pub(crate) use glyphs::glyphs_in_order;
-pub(crate) use hash::{extract_hash, hex_upper};
+pub(crate) use hash::{extract_hash, hash_with_files, hex_upper};
 pub(crate) use paths::{module_file_path, module_leaf, relative_path};
-pub(crate) use strings::{reserved_name, upper_first_char};
+pub(crate) use strings::{closest_matches, fmt_number, reserved_name, upper_first_char};
@@ -1,3 +1,5 @@
+use crate::Error;
+
 const RESERVED_WORDS: [&str; 52] = [
     "as", "async", "await", "break", "const", "continue", "crate", "else", "enum", "extern",
     "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
@@ -6,11 +8,14 @@ const RESERVED_WORDS: [&str; 52] = [
     "macro", "override", "priv", "try", "typeof", "unsized", "virtual", "yield",
 ];
 
-pub(crate) fn reserved_name(name: String) -> String {
+pub(crate) fn reserved_name(name: String) -> Result<String, Error> {
     if RESERVED_WORDS.contains(&name.as_str()) {
-        panic!("Reserved word used: {}", name);
+        return Err(Error::InvalidGlyph {
+            glyph: name,
+            message: "reserved word used".to_string(),
+        });
     }
-    name
+    Ok(name)
 }
 
 pub(crate) fn upper_first_char(raw: &str) -> String {
@@ -20,3 +25,64 @@ pub(crate) fn upper_first_char(raw: &str) -> String {
         None => String::new(),
     }
 }
+
+/// Format a number for SVG attributes without trailing zeros.
+pub(crate) fn fmt_number(value: f64) -> String {
+    let mut s = format!("{value:.6}");
+    while s.contains('.') && s.ends_with('0') {
+        s.pop();
+    }
+    if s.ends_with('.') {
+        s.pop();
+    }
+    if s.is_empty() {
+        s.push('0');
+    }
+    s
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let above = row[j + 1];
+            row[j + 1] = if ca == *cb {
+                diagonal
+            } else {
+                1 + diagonal.min(above).min(row[j])
+            };
+            diagonal = above;
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Up to `limit` candidates that look like a typo of `name` or contain it.
+pub(crate) fn closest_matches<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+    limit: usize,
+) -> Vec<String> {
+    let threshold = (name.chars().count() / 3).max(2);
+    let mut scored: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name)
+        .filter_map(|candidate| {
+            let distance = edit_distance(name, candidate);
+            (distance <= threshold || candidate.contains(name)).then_some((distance, candidate))
+        })
+        .collect();
+
+    scored.sort();
+    scored.dedup();
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}